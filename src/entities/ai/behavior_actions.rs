@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use bevy_rand_utils::RandomFromRange;
+
+use crate::entities::{projectiles::rotation_facing, weapons::spawn_weapon_projectile};
+
+use super::behavior_tree::{BehaviorActions, BehaviorContext, BlackboardValue, NodeStatus};
+
+const ARRIVAL_DISTANCE: f32 = 60.0;
+const FLEE_DISTANCE: f32 = 400.0;
+
+pub fn register_default_behavior_actions(mut actions: ResMut<BehaviorActions>) {
+    actions
+        .register("MoveTo", move_to)
+        .register("Wander", wander)
+        .register("Shoot", shoot)
+        .register("Flee", flee);
+}
+
+/// Moves the agent towards `destination`, returns true once it is within `ARRIVAL_DISTANCE`
+fn step_towards(context: &mut BehaviorContext, destination: Vec2) -> bool {
+    let to_destination = destination - context.transform.translation.truncate();
+
+    if to_destination.length() <= ARRIVAL_DISTANCE {
+        return true;
+    }
+
+    let step = context.movable.speed as f32 * context.delta_seconds;
+
+    context.transform.translation += (to_destination.normalize() * step).extend(0.0);
+
+    return false;
+}
+
+/// Moves towards the blackboard `target_position`
+pub fn move_to(context: &mut BehaviorContext) -> NodeStatus {
+    let destination = match context.blackboard.get_vec2("target_position") {
+        Some(destination) => destination,
+        None => return NodeStatus::Failure,
+    };
+
+    if step_towards(context, destination) {
+        NodeStatus::Success
+    } else {
+        NodeStatus::Running
+    }
+}
+
+/// Walks to a random point near the agent, picking a new one on arrival
+pub fn wander(context: &mut BehaviorContext) -> NodeStatus {
+    let position = context.transform.translation.truncate();
+
+    let destination = match context.blackboard.get_vec2("wander_target") {
+        Some(destination) => destination,
+        None => {
+            let destination = position + Vec2::new_random_signed(&80.0, &160.0);
+
+            context
+                .blackboard
+                .set("wander_target", BlackboardValue::Vec2(destination));
+
+            destination
+        }
+    };
+
+    if step_towards(context, destination) {
+        context.blackboard.remove("wander_target");
+
+        return NodeStatus::Success;
+    }
+
+    NodeStatus::Running
+}
+
+/// Fires the agent's weapon at the blackboard `target_position`
+pub fn shoot(context: &mut BehaviorContext) -> NodeStatus {
+    let target_position = match context.blackboard.get_vec2("target_position") {
+        Some(target_position) => target_position,
+        None => return NodeStatus::Failure,
+    };
+
+    let position = context.transform.translation.truncate();
+
+    let weapon = match context.weapon.as_deref_mut() {
+        Some(weapon) => weapon,
+        None => return NodeStatus::Failure,
+    };
+
+    if !weapon.try_fire() {
+        return NodeStatus::Running;
+    }
+
+    let aim = (target_position - position).normalize_or_zero();
+
+    spawn_weapon_projectile(
+        context.commands,
+        context.layers,
+        context.sprites,
+        weapon,
+        position,
+        rotation_facing(aim),
+    );
+
+    NodeStatus::Success
+}
+
+/// Runs away from the blackboard `target_position` until `FLEE_DISTANCE` is reached
+pub fn flee(context: &mut BehaviorContext) -> NodeStatus {
+    let threat = match context.blackboard.get_vec2("target_position") {
+        Some(threat) => threat,
+        None => return NodeStatus::Failure,
+    };
+
+    let away_from_threat = context.transform.translation.truncate() - threat;
+
+    if away_from_threat.length() >= FLEE_DISTANCE {
+        return NodeStatus::Success;
+    }
+
+    let step = context.movable.speed as f32 * context.delta_seconds;
+
+    context.transform.translation += (away_from_threat.normalize_or_zero() * step).extend(0.0);
+
+    NodeStatus::Running
+}
@@ -0,0 +1,610 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::entities::{
+    hud::UiFont,
+    layers::ZLayers,
+    shared::{DisplayName, Health, Movable},
+    sprites::SpriteDefinitions,
+    weapons::Weapon,
+};
+
+use super::perception::Perception;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeStatus {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlackboardValue {
+    Bool(bool),
+    Float(f32),
+    Vec2(Vec2),
+    Entity(Entity),
+}
+
+#[derive(Component, Default)]
+pub struct Blackboard {
+    values: HashMap<String, BlackboardValue>,
+}
+
+impl Blackboard {
+    pub fn set(&mut self, key: &str, value: BlackboardValue) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+
+    pub fn get(&self, key: &str) -> Option<BlackboardValue> {
+        self.values.get(key).copied()
+    }
+
+    /// One `key: value` line per entry, sorted by key
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .values
+            .iter()
+            .map(|(key, value)| format!("{}: {:?}", key, value))
+            .collect();
+
+        lines.sort();
+
+        return lines;
+    }
+
+    /// A key is set when it has a value other than `Bool(false)`
+    pub fn is_set(&self, key: &str) -> bool {
+        !matches!(self.get(key), None | Some(BlackboardValue::Bool(false)))
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f32> {
+        match self.get(key) {
+            Some(BlackboardValue::Float(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_vec2(&self, key: &str) -> Option<Vec2> {
+        match self.get(key) {
+            Some(BlackboardValue::Vec2(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Node layout as written in `.bt.ron` asset files
+#[derive(Clone, Debug, Deserialize)]
+pub enum BehaviorNodeDef {
+    Sequence(Vec<BehaviorNodeDef>),
+    Selector(Vec<BehaviorNodeDef>),
+    Inverter(Box<BehaviorNodeDef>),
+    Succeeder(Box<BehaviorNodeDef>),
+    Cooldown {
+        seconds: f32,
+        child: Box<BehaviorNodeDef>,
+    },
+    IsSet(String),
+    Below(String, f32),
+    Action(String),
+}
+
+#[derive(Deserialize)]
+struct BehaviorTreeDef {
+    root: BehaviorNodeDef,
+}
+
+#[derive(Clone, Debug)]
+pub enum BehaviorNode {
+    Sequence(Vec<usize>),
+    Selector(Vec<usize>),
+    Inverter(usize),
+    Succeeder(usize),
+    Cooldown { seconds: f32, child: usize },
+    IsSet(String),
+    Below(String, f32),
+    Action(String),
+}
+
+impl BehaviorNode {
+    pub fn label(&self) -> String {
+        match self {
+            BehaviorNode::Sequence(_) => "Sequence".to_string(),
+            BehaviorNode::Selector(_) => "Selector".to_string(),
+            BehaviorNode::Inverter(_) => "Inverter".to_string(),
+            BehaviorNode::Succeeder(_) => "Succeeder".to_string(),
+            BehaviorNode::Cooldown { seconds, .. } => format!("Cooldown {}s", seconds),
+            BehaviorNode::IsSet(key) => format!("IsSet {}", key),
+            BehaviorNode::Below(key, threshold) => format!("Below {} {}", key, threshold),
+            BehaviorNode::Action(name) => format!("Action {}", name),
+        }
+    }
+
+    pub fn children(&self) -> Vec<usize> {
+        match self {
+            BehaviorNode::Sequence(children) | BehaviorNode::Selector(children) => children.clone(),
+            BehaviorNode::Inverter(child)
+            | BehaviorNode::Succeeder(child)
+            | BehaviorNode::Cooldown { child, .. } => vec![*child],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Flattened behavior tree, nodes reference their children by index
+#[derive(TypeUuid)]
+#[uuid = "0e295754-2a70-44b3-8f09-000be5694856"]
+pub struct BehaviorTree {
+    pub nodes: Vec<BehaviorNode>,
+    pub root: usize,
+}
+
+impl BehaviorTree {
+    pub fn from_definition(root: &BehaviorNodeDef) -> Self {
+        let mut tree = BehaviorTree {
+            nodes: Vec::new(),
+            root: 0,
+        };
+
+        tree.root = tree.add_node(root);
+
+        tree
+    }
+
+    fn add_node(&mut self, definition: &BehaviorNodeDef) -> usize {
+        let node = match definition {
+            BehaviorNodeDef::Sequence(children) => {
+                BehaviorNode::Sequence(children.iter().map(|child| self.add_node(child)).collect())
+            }
+            BehaviorNodeDef::Selector(children) => {
+                BehaviorNode::Selector(children.iter().map(|child| self.add_node(child)).collect())
+            }
+            BehaviorNodeDef::Inverter(child) => BehaviorNode::Inverter(self.add_node(child)),
+            BehaviorNodeDef::Succeeder(child) => BehaviorNode::Succeeder(self.add_node(child)),
+            BehaviorNodeDef::Cooldown { seconds, child } => BehaviorNode::Cooldown {
+                seconds: *seconds,
+                child: self.add_node(child),
+            },
+            BehaviorNodeDef::IsSet(key) => BehaviorNode::IsSet(key.clone()),
+            BehaviorNodeDef::Below(key, threshold) => BehaviorNode::Below(key.clone(), *threshold),
+            BehaviorNodeDef::Action(name) => BehaviorNode::Action(name.clone()),
+        };
+
+        self.nodes.push(node);
+
+        self.nodes.len() - 1
+    }
+
+    pub fn tick(
+        &self,
+        state: &mut BehaviorTreeState,
+        context: &mut BehaviorContext,
+        actions: &BehaviorActions,
+    ) -> NodeStatus {
+        state.statuses.clear();
+        state.statuses.resize(self.nodes.len(), None);
+
+        self.tick_node(self.root, state, context, actions)
+    }
+
+    fn tick_node(
+        &self,
+        index: usize,
+        state: &mut BehaviorTreeState,
+        context: &mut BehaviorContext,
+        actions: &BehaviorActions,
+    ) -> NodeStatus {
+        let status = match &self.nodes[index] {
+            BehaviorNode::Sequence(children) => {
+                let mut status = NodeStatus::Success;
+
+                for child in children.iter() {
+                    status = self.tick_node(*child, state, context, actions);
+
+                    if status != NodeStatus::Success {
+                        break;
+                    }
+                }
+
+                status
+            }
+            BehaviorNode::Selector(children) => {
+                let mut status = NodeStatus::Failure;
+
+                for child in children.iter() {
+                    status = self.tick_node(*child, state, context, actions);
+
+                    if status != NodeStatus::Failure {
+                        break;
+                    }
+                }
+
+                status
+            }
+            BehaviorNode::Inverter(child) => match self.tick_node(*child, state, context, actions) {
+                NodeStatus::Success => NodeStatus::Failure,
+                NodeStatus::Failure => NodeStatus::Success,
+                NodeStatus::Running => NodeStatus::Running,
+            },
+            BehaviorNode::Succeeder(child) => {
+                self.tick_node(*child, state, context, actions);
+
+                NodeStatus::Success
+            }
+            BehaviorNode::Cooldown { seconds, child } => {
+                let ready_at = state.cooldowns.get(&index).copied().unwrap_or(0.0);
+
+                if context.elapsed_seconds < ready_at {
+                    NodeStatus::Failure
+                } else {
+                    let status = self.tick_node(*child, state, context, actions);
+
+                    if status == NodeStatus::Success {
+                        state
+                            .cooldowns
+                            .insert(index, context.elapsed_seconds + *seconds as f64);
+                    }
+
+                    status
+                }
+            }
+            BehaviorNode::IsSet(key) => {
+                if context.blackboard.is_set(key) {
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Failure
+                }
+            }
+            BehaviorNode::Below(key, threshold) => match context.blackboard.get_float(key) {
+                Some(value) if value < *threshold => NodeStatus::Success,
+                _ => NodeStatus::Failure,
+            },
+            BehaviorNode::Action(name) => match actions.get(name) {
+                Some(action) => action(context),
+                None => {
+                    warn!("Behavior tree action {} is not registered", name);
+
+                    NodeStatus::Failure
+                }
+            },
+        };
+
+        state.statuses[index] = Some(status);
+
+        status
+    }
+
+    /// Label of the node the agent is busy with, the deepest running one or else the last one
+    /// that ran during the latest tick
+    fn active_node(&self, state: &BehaviorTreeState) -> Option<String> {
+        let mut running = None;
+        let mut evaluated = None;
+
+        self.find_active_node(self.root, state, &mut running, &mut evaluated);
+
+        running.or(evaluated).map(|index| self.nodes[index].label())
+    }
+
+    fn find_active_node(
+        &self,
+        index: usize,
+        state: &BehaviorTreeState,
+        running: &mut Option<usize>,
+        evaluated: &mut Option<usize>,
+    ) {
+        match state.statuses.get(index).copied().flatten() {
+            Some(NodeStatus::Running) => *running = Some(index),
+            Some(_) => {}
+            None => return,
+        }
+
+        *evaluated = Some(index);
+
+        for child in self.nodes[index].children() {
+            self.find_active_node(child, state, running, evaluated);
+        }
+    }
+
+    fn describe(&self, index: usize, depth: usize, state: &BehaviorTreeState, lines: &mut Vec<String>) {
+        let status = match state.statuses.get(index).copied().flatten() {
+            Some(status) => format!("{:?}", status),
+            None => "-".to_string(),
+        };
+
+        lines.push(format!("{}{} [{}]", "  ".repeat(depth), self.nodes[index].label(), status));
+
+        for child in self.nodes[index].children() {
+            self.describe(child, depth + 1, state, lines);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BehaviorTreeLoader;
+
+impl AssetLoader for BehaviorTreeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<BehaviorTreeDef>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(BehaviorTree::from_definition(&definition.root)));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bt.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct BehaviorTreeState {
+    cooldowns: HashMap<usize, f64>,
+    statuses: Vec<Option<NodeStatus>>,
+}
+
+#[derive(Component)]
+pub struct BehaviorTreeAgent {
+    pub path: String,
+    pub tree: Option<Handle<BehaviorTree>>,
+    pub state: BehaviorTreeState,
+}
+
+impl BehaviorTreeAgent {
+    pub fn new(path: &str) -> Self {
+        BehaviorTreeAgent {
+            path: path.to_string(),
+            tree: None,
+            state: BehaviorTreeState::default(),
+        }
+    }
+}
+
+/// Everything a leaf action may read or change on the agent it runs for
+pub struct BehaviorContext<'a, 'w, 's> {
+    pub entity: Entity,
+    pub transform: &'a mut Transform,
+    pub movable: &'a Movable,
+    pub blackboard: &'a mut Blackboard,
+    pub weapon: Option<&'a mut Weapon>,
+    pub commands: &'a mut Commands<'w, 's>,
+    pub layers: &'a ZLayers,
+    pub sprites: &'a SpriteDefinitions,
+    pub delta_seconds: f32,
+    pub elapsed_seconds: f64,
+}
+
+pub type BehaviorAction = fn(&mut BehaviorContext) -> NodeStatus;
+
+#[derive(Default)]
+pub struct BehaviorActions {
+    actions: HashMap<String, BehaviorAction>,
+}
+
+impl BehaviorActions {
+    pub fn register(&mut self, name: &str, action: BehaviorAction) -> &mut Self {
+        self.actions.insert(name.to_string(), action);
+
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<BehaviorAction> {
+        self.actions.get(name).copied()
+    }
+}
+
+/// Labels above every agent with its active node and blackboard, the whole trees are logged
+pub struct BehaviorTreeDebugView {
+    pub enabled: bool,
+    pub refresh: Timer,
+}
+
+impl Default for BehaviorTreeDebugView {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            refresh: Timer::from_seconds(0.25, true),
+        }
+    }
+}
+
+/// World space text following the agent it describes
+#[derive(Component)]
+pub struct BehaviorTreeDebugLabel {
+    agent: Entity,
+}
+
+const DEBUG_LABEL_OFFSET: Vec2 = Vec2::new(0.0, 56.0);
+
+#[allow(clippy::type_complexity)]
+pub fn update_blackboards(
+    mut agents: Query<(&mut Blackboard, Option<&Perception>, Option<&Health>)>,
+) {
+    for (mut blackboard, perception, health) in agents.iter_mut() {
+        match perception.and_then(|perception| perception.target.as_ref()) {
+            Some(target) => {
+                blackboard.set("target_position", BlackboardValue::Vec2(target.position));
+                blackboard.set("target_visible", BlackboardValue::Bool(target.visible));
+            }
+            None => {
+                blackboard.remove("target_position");
+                blackboard.set("target_visible", BlackboardValue::Bool(false));
+            }
+        }
+
+        if let Some(health) = health {
+            let health_fraction = health.current_health as f32 / health.max_health as f32;
+
+            blackboard.set("health_fraction", BlackboardValue::Float(health_fraction));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn run_behavior_trees(
+    mut agents: Query<(
+        Entity,
+        &mut BehaviorTreeAgent,
+        &mut Blackboard,
+        &mut Transform,
+        &Movable,
+        Option<&mut Weapon>,
+    )>,
+    trees: Res<Assets<BehaviorTree>>,
+    actions: Res<BehaviorActions>,
+    asset_server: Res<AssetServer>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut agent, mut blackboard, mut transform, movable, mut weapon) in agents.iter_mut() {
+        let agent = &mut *agent;
+
+        if agent.tree.is_none() {
+            agent.tree = Some(asset_server.load(agent.path.as_str()));
+        }
+
+        let tree = match agent.tree.as_ref().and_then(|handle| trees.get(handle)) {
+            Some(tree) => tree,
+            None => continue,
+        };
+
+        let mut context = BehaviorContext {
+            entity,
+            transform: &mut transform,
+            movable,
+            blackboard: &mut blackboard,
+            weapon: weapon.as_deref_mut(),
+            commands: &mut commands,
+            layers: &layers,
+            sprites: &sprites,
+            delta_seconds: time.delta_seconds(),
+            elapsed_seconds: time.seconds_since_startup(),
+        };
+
+        tree.tick(&mut agent.state, &mut context, &actions);
+    }
+}
+
+pub fn toggle_behavior_tree_debug_view(
+    keys: Res<Input<KeyCode>>,
+    mut debug_view: ResMut<BehaviorTreeDebugView>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        debug_view.enabled = !debug_view.enabled;
+
+        info!("Behavior tree debug view {}", if debug_view.enabled { "enabled" } else { "disabled" });
+    }
+}
+
+fn debug_label_text(
+    tree: Option<&BehaviorTree>,
+    agent: &BehaviorTreeAgent,
+    blackboard: &Blackboard,
+) -> String {
+    let active_node = tree
+        .and_then(|tree| tree.active_node(&agent.state))
+        .unwrap_or_else(|| "-".to_string());
+
+    let mut lines = vec![active_node];
+
+    lines.extend(blackboard.describe());
+
+    return lines.join("\n");
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn show_behavior_tree_debug_view(
+    agents: Query<(Entity, &DisplayName, &GlobalTransform, &BehaviorTreeAgent, &Blackboard)>,
+    mut labels: Query<(Entity, &BehaviorTreeDebugLabel, &mut Text, &mut Transform)>,
+    trees: Res<Assets<BehaviorTree>>,
+    mut debug_view: ResMut<BehaviorTreeDebugView>,
+    font: Res<UiFont>,
+    layers: Res<ZLayers>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    if !debug_view.enabled {
+        for (label, _, _, _) in labels.iter() {
+            commands.entity(label).despawn();
+        }
+
+        return;
+    }
+
+    let refresh = debug_view.refresh.tick(time.delta()).just_finished();
+
+    let mut labelled = HashSet::new();
+
+    for (label, debug_label, mut text, mut transform) in labels.iter_mut() {
+        let (_, _, agent_transform, agent, blackboard) = match agents.get(debug_label.agent) {
+            Ok(agent) => agent,
+            Err(_) => {
+                commands.entity(label).despawn();
+
+                continue;
+            }
+        };
+
+        labelled.insert(debug_label.agent);
+
+        let position = agent_transform.translation().truncate() + DEBUG_LABEL_OFFSET;
+
+        transform.translation = position.extend(layers.ui);
+
+        if refresh {
+            let tree = agent.tree.as_ref().and_then(|handle| trees.get(handle));
+
+            text.sections[0].value = debug_label_text(tree, agent, blackboard);
+        }
+    }
+
+    for (entity, name, agent_transform, agent, blackboard) in agents.iter() {
+        let tree = agent.tree.as_ref().and_then(|handle| trees.get(handle));
+
+        if refresh {
+            if let Some(tree) = tree {
+                let mut lines = vec![format!("{} ({})", name.0, agent.path)];
+
+                tree.describe(tree.root, 1, &agent.state, &mut lines);
+
+                debug!("{}", lines.join("\n"));
+            }
+        }
+
+        if labelled.contains(&entity) {
+            continue;
+        }
+
+        let position = agent_transform.translation().truncate() + DEBUG_LABEL_OFFSET;
+
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::from_section(
+                    debug_label_text(tree, agent, blackboard),
+                    font.style(14.0, Color::YELLOW),
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_translation(position.extend(layers.ui)),
+                ..Default::default()
+            })
+            .insert(BehaviorTreeDebugLabel { agent: entity });
+    }
+}
@@ -4,13 +4,27 @@ use bevy::prelude::*;
 use bevy_rand_utils::prelude::*;
 use bevy_transform_utils::move_towards;
 
-use crate::entities::{enemy::*, shared::Movable};
+use crate::entities::{
+    enemy::*,
+    hq::Headquarters,
+    navigation::{NavGrid, NavRoute},
+    shared::Movable,
+};
+
+use super::{perception::Perception, squad::SquadMember};
+
+const IDLE_MARCH_STEP: f32 = 100.0;
+
+/// Distance at which a path waypoint counts as reached
+const WAYPOINT_REACHED_DISTANCE: f32 = 64.0;
 
 #[derive(Component)]
 pub struct Idle {
     pub delay: Timer,
     pub idle_move: Vec3,
     pub idle_walk_distance: i32,
+    /// Route around blockers to the current objective
+    pub route: NavRoute,
 }
 
 impl Default for Idle {
@@ -19,18 +33,66 @@ impl Default for Idle {
             delay: Timer::new(Duration::from_secs(4), true), // TODO havin same duration feels unnatural, implement propper random time intervals which are adjusted more easily
             idle_move: Default::default(),
             idle_walk_distance: 200,
+            route: NavRoute::default(),
+        }
+    }
+}
+
+/// Waypoints idle enemies march along before heading for the headquarters
+#[derive(Component)]
+pub struct FollowPath {
+    pub waypoints: Vec<Vec2>,
+    pub next: usize,
+}
+
+impl FollowPath {
+    pub fn new(waypoints: Vec<Vec2>) -> Self {
+        FollowPath { waypoints, next: 0 }
+    }
+
+    /// Waypoint to head for from `position`, skipping the ones already reached
+    fn next_waypoint(&mut self, position: Vec2) -> Option<Vec2> {
+        while let Some(waypoint) = self.waypoints.get(self.next) {
+            if waypoint.distance(position) > WAYPOINT_REACHED_DISTANCE {
+                return Some(*waypoint);
+            }
+
+            self.next += 1;
         }
+
+        None
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn idle_enemy_behaviour(
     mut query: Query<
-        (&mut Transform, &mut Idle, &Movable),
+        (&mut Transform, &mut Idle, &Movable, Option<&mut FollowPath>),
         (With<Enemy>, With<Idle>, With<Movable>),
     >,
+    headquarters: Query<&GlobalTransform, With<Headquarters>>,
+    nav_grid: Res<NavGrid>,
     time: Res<Time>,
 ) {
-    for (mut transform, mut idle_state, movable) in query.iter_mut() {
+    let grid_changed = nav_grid.is_changed();
+
+    let hq_position = headquarters
+        .get_single()
+        .ok()
+        .map(|hq_transform| hq_transform.translation().truncate());
+
+    for (mut transform, mut idle_state, movable, path) in query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let objective = path
+            .and_then(|mut path| path.next_waypoint(position))
+            .or(hq_position)
+            .map(|objective| {
+                idle_state
+                    .route
+                    .steer(&nav_grid, position, objective, grid_changed)
+            });
+
         if !idle_state.delay.finished() {
             idle_state.delay.tick(time.delta());
         } else {
@@ -42,7 +104,14 @@ pub fn idle_enemy_behaviour(
                 40.0,
             ) {
             } else {
-                let new_idle_coords = Vec2::new_random_signed(&50.0, &80.0);
+                let mut new_idle_coords = Vec2::new_random_signed(&50.0, &80.0);
+
+                // idle enemies drift towards the headquarters until they perceive something to attack
+                if let Some(objective) = objective {
+                    new_idle_coords += (objective - transform.translation.truncate())
+                        .normalize_or_zero()
+                        * IDLE_MARCH_STEP;
+                }
 
                 let new_idle_pos = Vec3::from((new_idle_coords, idle_state.idle_move.z)); // Do not randomize z
 
@@ -55,3 +124,59 @@ pub fn idle_enemy_behaviour(
         }
     }
 }
+
+#[derive(Component)]
+pub struct Chase {
+    pub stop_distance: f32,
+}
+
+impl Default for Chase {
+    fn default() -> Self {
+        Self { stop_distance: 80.0 }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn transition_enemy_ai_on_perception(
+    query: Query<
+        (Entity, &Transform, &Perception, Option<&Idle>, Option<&Chase>),
+        (With<Enemy>, Without<SquadMember>),
+    >,
+    mut commands: Commands,
+) {
+    for (entity, transform, perception, idle, chase) in query.iter() {
+        let has_target = perception.target.is_some();
+
+        if has_target && idle.is_some() {
+            commands
+                .entity(entity)
+                .remove::<Idle>()
+                .insert(Chase::default());
+        } else if !has_target && chase.is_some() {
+            commands.entity(entity).remove::<Chase>().insert(Idle {
+                idle_move: transform.translation,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn chase_perceived_targets(
+    mut query: Query<(&mut Transform, &Chase, &Perception, &Movable), With<Enemy>>,
+    time: Res<Time>,
+) {
+    for (mut transform, chase, perception, movable) in query.iter_mut() {
+        if let Some(target_position) = perception.target_position() {
+            let destination = target_position.extend(transform.translation.z);
+
+            move_towards(
+                &mut transform,
+                destination,
+                movable.speed as f32,
+                &time,
+                chase.stop_distance,
+            );
+        }
+    }
+}
@@ -1 +1,6 @@
+pub mod behavior_actions;
+pub mod behavior_tree;
 pub mod enemy_ai;
+pub mod perception;
+pub mod squad;
+pub mod tank_ai;
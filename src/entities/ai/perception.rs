@@ -0,0 +1,299 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::entities::shared::Collider;
+
+#[derive(Clone)]
+pub struct PerceivedTarget {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub visible: bool,
+    pub forget_timer: Timer,
+}
+
+#[derive(Component)]
+pub struct Perception {
+    pub vision_range: f32,
+    /// Full opening angle of the vision cone centered on `facing`
+    pub vision_cone_rad: f32,
+    /// How long a target is remembered after it was last seen or heard
+    pub memory_sec: f32,
+    pub facing: Vec2,
+    pub target: Option<PerceivedTarget>,
+    last_position: Option<Vec2>,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            vision_range: 300.0,
+            vision_cone_rad: PI / 2.0,
+            memory_sec: 3.0,
+            facing: Vec2::Y,
+            target: None,
+            last_position: None,
+        }
+    }
+}
+
+impl Perception {
+    pub fn new(vision_range: f32, vision_cone_rad: f32) -> Self {
+        Perception {
+            vision_range,
+            vision_cone_rad,
+            ..Default::default()
+        }
+    }
+
+    pub fn can_see(&self, observer: Vec2, position: Vec2) -> bool {
+        let to_position = position - observer;
+
+        if to_position.length() > self.vision_range {
+            return false;
+        }
+
+        if self.vision_cone_rad >= 2.0 * PI || to_position == Vec2::ZERO || self.facing == Vec2::ZERO {
+            return true;
+        }
+
+        self.facing.angle_between(to_position).abs() <= self.vision_cone_rad / 2.0
+    }
+
+    pub fn target_position(&self) -> Option<Vec2> {
+        self.target.as_ref().map(|target| target.position)
+    }
+
+    fn remember(&mut self, entity: Entity, position: Vec2, visible: bool) {
+        self.target = Some(PerceivedTarget {
+            entity,
+            position,
+            visible,
+            forget_timer: Timer::from_seconds(self.memory_sec, false),
+        });
+    }
+}
+
+/// Marks entities that AI perception looks for
+#[derive(Component, Default)]
+pub struct PerceptionTarget;
+
+/// Marks entities that block line of sight with their sprite bounds
+#[derive(Component, Default)]
+pub struct BlocksSight;
+
+pub struct GunfireEvent {
+    /// Tank or tower that fired, observers remember it as their target
+    pub shooter: Entity,
+    pub position: Vec2,
+    pub audible_radius: f32,
+}
+
+pub fn segment_intersects_aabb(from: Vec2, to: Vec2, center: Vec2, half_size: Vec2) -> bool {
+    let direction = to - from;
+
+    let mut t_min = 0.0_f32;
+    let mut t_max = 1.0_f32;
+
+    for axis in 0..2 {
+        let min = center[axis] - half_size[axis];
+        let max = center[axis] + half_size[axis];
+
+        if direction[axis].abs() < f32::EPSILON {
+            if from[axis] < min || from[axis] > max {
+                return false;
+            }
+        } else {
+            let mut t_enter = (min - from[axis]) / direction[axis];
+            let mut t_exit = (max - from[axis]) / direction[axis];
+
+            if t_enter > t_exit {
+                std::mem::swap(&mut t_enter, &mut t_exit);
+            }
+
+            t_min = t_min.max(t_enter);
+            t_max = t_max.min(t_exit);
+
+            if t_min > t_max {
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+pub fn has_line_of_sight<'a>(
+    from: Vec2,
+    to: Vec2,
+    mut blockers: impl Iterator<Item = (&'a GlobalTransform, &'a Collider)>,
+) -> bool {
+    !blockers.any(|(blocker_transform, blocker_collider)| {
+        let half_size = blocker_collider.size / 2.0;
+
+        segment_intersects_aabb(from, to, blocker_transform.translation().truncate(), half_size)
+    })
+}
+
+pub fn update_perception_facing(mut observers: Query<(&GlobalTransform, &mut Perception)>) {
+    for (global_transform, mut perception) in observers.iter_mut() {
+        let position = global_transform.translation().truncate();
+
+        if let Some(last_position) = perception.last_position {
+            let movement = position - last_position;
+
+            if movement.length() > f32::EPSILON {
+                perception.facing = movement.normalize();
+            }
+        }
+
+        perception.last_position = Some(position);
+    }
+}
+
+pub fn perceive_targets_by_sight(
+    mut observers: Query<(&GlobalTransform, &mut Perception)>,
+    targets: Query<(Entity, &GlobalTransform), With<PerceptionTarget>>,
+    blockers: Query<(&GlobalTransform, &Collider), With<BlocksSight>>,
+    time: Res<Time>,
+) {
+    for (global_transform, mut perception) in observers.iter_mut() {
+        let observer = global_transform.translation().truncate();
+
+        let seen_target = targets
+            .iter()
+            .map(|(entity, target_transform)| (entity, target_transform.translation().truncate()))
+            .filter(|(_, position)| perception.can_see(observer, *position))
+            .filter(|(_, position)| has_line_of_sight(observer, *position, blockers.iter()))
+            .min_by(|(_, a), (_, b)| a.distance(observer).total_cmp(&b.distance(observer)));
+
+        if let Some((entity, position)) = seen_target {
+            perception.remember(entity, position, true);
+            perception.facing = (position - observer).normalize_or_zero();
+
+            continue;
+        }
+
+        let forget = match perception.target.as_mut() {
+            Some(target) => {
+                target.visible = false;
+
+                target.forget_timer.tick(time.delta()).finished()
+            }
+            None => false,
+        };
+
+        if forget {
+            perception.target = None;
+        }
+    }
+}
+
+pub fn perceive_gunfire(
+    mut gunfire: EventReader<GunfireEvent>,
+    mut observers: Query<(&GlobalTransform, &mut Perception)>,
+) {
+    for shot in gunfire.iter() {
+        for (global_transform, mut perception) in observers.iter_mut() {
+            let observer = global_transform.translation().truncate();
+
+            if observer.distance(shot.position) > shot.audible_radius {
+                continue;
+            }
+
+            let already_visible = perception
+                .target
+                .as_ref()
+                .map(|target| target.visible)
+                .unwrap_or(false);
+
+            if !already_visible {
+                perception.remember(shot.shooter, shot.position, false);
+                perception.facing = (shot.position - observer).normalize_or_zero();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(degrees: f32) -> Vec2 {
+        let radians = degrees.to_radians();
+
+        Vec2::new(radians.cos(), radians.sin())
+    }
+
+    #[test]
+    fn segment_through_box_intersects() {
+        let center = Vec2::new(50.0, 0.0);
+        let half_size = Vec2::splat(10.0);
+
+        assert!(segment_intersects_aabb(
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            center,
+            half_size
+        ));
+        assert!(segment_intersects_aabb(
+            Vec2::new(0.0, -50.0),
+            Vec2::new(100.0, 50.0),
+            center,
+            half_size
+        ));
+    }
+
+    #[test]
+    fn segment_missing_or_short_of_box_does_not_intersect() {
+        let center = Vec2::new(50.0, 0.0);
+        let half_size = Vec2::splat(10.0);
+
+        // passes above the box
+        assert!(!segment_intersects_aabb(
+            Vec2::new(0.0, 20.0),
+            Vec2::new(100.0, 20.0),
+            center,
+            half_size
+        ));
+        // ends before the box
+        assert!(!segment_intersects_aabb(
+            Vec2::ZERO,
+            Vec2::new(30.0, 0.0),
+            center,
+            half_size
+        ));
+        // vertical segment beside the box
+        assert!(!segment_intersects_aabb(
+            Vec2::new(0.0, -50.0),
+            Vec2::new(0.0, 50.0),
+            center,
+            half_size
+        ));
+    }
+
+    #[test]
+    fn sees_only_within_range() {
+        let perception = Perception::new(100.0, 2.0 * PI);
+
+        assert!(perception.can_see(Vec2::ZERO, Vec2::new(0.0, 99.0)));
+        assert!(!perception.can_see(Vec2::ZERO, Vec2::new(0.0, 101.0)));
+        assert!(perception.can_see(Vec2::ZERO, Vec2::new(0.0, -50.0)));
+    }
+
+    #[test]
+    fn sees_only_within_vision_cone() {
+        let mut perception = Perception::new(300.0, PI / 2.0);
+
+        perception.facing = Vec2::Y;
+
+        let observer = Vec2::new(10.0, 10.0);
+
+        assert!(perception.can_see(observer, observer + Vec2::new(0.0, 100.0)));
+        // 40 degrees off the facing, inside the 90 degree cone
+        assert!(perception.can_see(observer, observer + direction(50.0) * 100.0));
+        // 60 degrees off the facing, outside of it
+        assert!(!perception.can_see(observer, observer + direction(30.0) * 100.0));
+        assert!(!perception.can_see(observer, observer + Vec2::new(0.0, -100.0)));
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use bevy::{math::Mat2, prelude::*};
+use bevy_rand_utils::RandomFromRange;
+
+use crate::entities::{
+    bounds::WorldBounds,
+    difficulty::DifficultyModifiers,
+    enemy::{Enemy, EnemyArchetype},
+    layers::ZLayers,
+    shared::{DamageEvent, Movable},
+    spawner::spawn_enemy,
+    sprites::SpriteDefinitions,
+};
+
+use super::{
+    behavior_tree::{BehaviorTreeAgent, Blackboard},
+    enemy_ai::{Chase, Idle},
+    perception::Perception,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Formation {
+    Line,
+    Wedge,
+    Column,
+}
+
+impl Formation {
+    /// Offset of a follower slot from its leader, in the leader's frame where +Y is the facing direction
+    pub fn slot_offset(&self, slot: usize, spacing: f32) -> Vec2 {
+        let rank = (slot / 2 + 1) as f32;
+        let side = if slot % 2 == 0 { 1.0 } else { -1.0 };
+
+        match self {
+            Formation::Line => Vec2::new(side * rank * spacing, 0.0),
+            Formation::Wedge => Vec2::new(side * rank * spacing, -rank * spacing),
+            Formation::Column => Vec2::new(0.0, -((slot + 1) as f32) * spacing),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SquadSpawn {
+    pub formation: Formation,
+    pub archetype: EnemyArchetype,
+    pub followers: usize,
+}
+
+#[derive(Component)]
+pub struct SquadLeader {
+    pub formation: Formation,
+    pub spacing: f32,
+}
+
+#[derive(Component)]
+pub struct SquadMember {
+    pub leader: Entity,
+    pub slot: usize,
+    pub formation: Formation,
+    pub spacing: f32,
+}
+
+/// Follower that broke formation after its squad was attacked
+#[derive(Component)]
+pub struct Engaged {
+    pub timer: Timer,
+}
+
+impl Default for Engaged {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(5.0, false),
+        }
+    }
+}
+
+const DEFAULT_SPACING: f32 = 80.0;
+
+/// Spawns the leader and its followers around `position`, returns every member with the leader first
+pub fn spawn_squad(
+    squad: &SquadSpawn,
+    position: Vec2,
+    modifiers: &DifficultyModifiers,
+    bounds: &WorldBounds,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Vec<Entity> {
+    let leader = spawn_enemy(squad.archetype, position, modifiers, layers, sprites, commands);
+
+    commands.entity(leader).insert(SquadLeader {
+        formation: squad.formation,
+        spacing: DEFAULT_SPACING,
+    });
+
+    let mut members = vec![leader];
+
+    for slot in 0..squad.followers {
+        let offset = Vec2::new_random_signed(&20.0, &60.0);
+
+        let follower = spawn_enemy(
+            squad.archetype,
+            bounds.clamp_spawn(position + offset),
+            modifiers,
+            layers,
+            sprites,
+            commands,
+        );
+
+        commands
+            .entity(follower)
+            .remove::<Idle>()
+            .remove::<BehaviorTreeAgent>()
+            .insert(SquadMember {
+                leader,
+                slot,
+                formation: squad.formation,
+                spacing: DEFAULT_SPACING,
+            });
+
+        members.push(follower);
+    }
+
+    members
+}
+
+#[allow(clippy::type_complexity)]
+pub fn follow_squad_leaders(
+    mut followers: Query<(&mut Transform, &SquadMember, &Movable), Without<Engaged>>,
+    leaders: Query<(&Transform, &Perception), (With<SquadLeader>, Without<SquadMember>)>,
+    time: Res<Time>,
+) {
+    for (mut transform, member, movable) in followers.iter_mut() {
+        let (leader_transform, leader_perception) = match leaders.get(member.leader) {
+            Ok(leader) => leader,
+            Err(_) => continue,
+        };
+
+        let leader_facing = if leader_perception.facing == Vec2::ZERO {
+            Vec2::Y
+        } else {
+            leader_perception.facing
+        };
+
+        let leader_rotation = Mat2::from_angle(Vec2::Y.angle_between(leader_facing));
+
+        let slot_position = leader_transform.translation.truncate()
+            + leader_rotation * member.formation.slot_offset(member.slot, member.spacing);
+
+        let to_slot = slot_position - transform.translation.truncate();
+
+        let step = movable.speed as f32 * time.delta_seconds();
+
+        if to_slot.length() > step {
+            transform.translation += (to_slot.normalize() * step).extend(0.0);
+        } else {
+            transform.translation += to_slot.extend(0.0);
+        }
+    }
+}
+
+/// The follower in the first slot takes over, with the AI its archetype spawns with
+pub fn promote_squad_leaders(
+    mut members: Query<(Entity, &Transform, &EnemyArchetype, &mut SquadMember)>,
+    leaders: Query<(), With<SquadLeader>>,
+    mut commands: Commands,
+) {
+    let mut orphaned_squads: HashMap<Entity, Vec<(usize, Entity)>> = HashMap::new();
+
+    for (entity, _, _, member) in members.iter() {
+        if leaders.get(member.leader).is_err() {
+            orphaned_squads
+                .entry(member.leader)
+                .or_default()
+                .push((member.slot, entity));
+        }
+    }
+
+    for (_, mut squad) in orphaned_squads.into_iter() {
+        squad.sort();
+
+        let (_, new_leader) = squad.remove(0);
+
+        if let Ok((_, transform, archetype, member)) = members.get(new_leader) {
+            let mut leader_commands = commands.entity(new_leader);
+
+            leader_commands
+                .remove::<SquadMember>()
+                .remove::<Engaged>()
+                .remove::<Chase>()
+                .insert(SquadLeader {
+                    formation: member.formation,
+                    spacing: member.spacing,
+                });
+
+            // enemy tanks keep their own AI while following, the others get back what they spawned with
+            if let Some(behavior_tree) = archetype.behavior_tree() {
+                leader_commands
+                    .insert(BehaviorTreeAgent::new(behavior_tree))
+                    .insert(Blackboard::default());
+            } else if *archetype != EnemyArchetype::Tank {
+                leader_commands.insert(Idle {
+                    idle_move: transform.translation,
+                    ..Default::default()
+                });
+            }
+        }
+
+        for (slot, (_, follower)) in squad.into_iter().enumerate() {
+            if let Ok((_, _, _, mut member)) = members.get_mut(follower) {
+                member.leader = new_leader;
+                member.slot = slot;
+            }
+        }
+
+        info!("Squad leader died, promoted {:?}", new_leader);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn break_formation_when_attacked(
+    mut damage_events: EventReader<DamageEvent>,
+    damaged_squads: Query<(Option<&SquadMember>, Option<&SquadLeader>), With<Enemy>>,
+    followers: Query<(Entity, &SquadMember)>,
+    mut commands: Commands,
+) {
+    for damage in damage_events.iter() {
+        let squad_leader = match damaged_squads.get(damage.target) {
+            Ok((Some(member), _)) => member.leader,
+            Ok((None, Some(_))) => damage.target,
+            _ => continue,
+        };
+
+        for (follower, member) in followers.iter() {
+            if member.leader == squad_leader {
+                commands
+                    .entity(follower)
+                    .insert(Engaged::default())
+                    .insert(Chase::default());
+            }
+        }
+    }
+}
+
+pub fn regroup_engaged_followers(
+    mut followers: Query<(Entity, &mut Engaged, &Perception), With<SquadMember>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut engaged, perception) in followers.iter_mut() {
+        engaged.timer.tick(time.delta());
+
+        if engaged.timer.finished() && perception.target.is_none() {
+            commands.entity(entity).remove::<Engaged>().remove::<Chase>();
+        }
+    }
+}
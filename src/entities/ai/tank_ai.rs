@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use bevy_rand_utils::RandomFromRange;
+use bevy_transform_utils::get_angle_from_transform;
+use leafwing_input_manager::prelude::*;
+
+use crate::entities::{
+    enemy::Enemy,
+    hq::Headquarters,
+    player::{PlayerAction, TankTurret},
+    shared::Movable,
+    weapons::Weapon,
+};
+
+use super::perception::Perception;
+
+/// Angle to the destination below which the tank stops turning
+const TURN_TOLERANCE_RAD: f32 = 0.1;
+
+/// Angle to the destination below which the tank drives forward while turning
+const DRIVE_CONE_RAD: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Drives an enemy tank by writing its `ActionState<PlayerAction>` instead of moving it directly
+#[derive(Component)]
+pub struct EnemyTankAi {
+    pub stop_distance: f32,
+    pub fire_range: f32,
+    pub wander_target: Option<Vec2>,
+}
+
+impl Default for EnemyTankAi {
+    fn default() -> Self {
+        Self {
+            stop_distance: 200.0,
+            fire_range: 350.0,
+            wander_target: None,
+        }
+    }
+}
+
+/// Point the turret rotates towards, the AI counterpart of the mouse cursor
+#[derive(Component, Default)]
+pub struct TurretAim {
+    pub target: Option<Vec2>,
+}
+
+fn set_action(action_state: &mut ActionState<PlayerAction>, action: PlayerAction, pressed: bool) {
+    if pressed {
+        action_state.press(action);
+    } else {
+        action_state.release(action);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn drive_enemy_tanks(
+    mut tanks: Query<
+        (
+            &Transform,
+            &Perception,
+            &mut EnemyTankAi,
+            &mut ActionState<PlayerAction>,
+            &Children,
+        ),
+        With<Enemy>,
+    >,
+    mut turrets: Query<
+        (&mut TurretAim, &Weapon, &mut ActionState<PlayerAction>),
+        (With<TankTurret>, Without<Enemy>),
+    >,
+    headquarters: Query<&GlobalTransform, With<Headquarters>>,
+) {
+    let objective = headquarters
+        .get_single()
+        .ok()
+        .map(|hq_transform| hq_transform.translation().truncate());
+
+    for (transform, perception, mut ai, mut action_state, children) in tanks.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let destination = match (perception.target_position(), objective) {
+            (Some(target_position), _) => target_position,
+            (None, Some(objective)) => objective,
+            (None, None) => *ai
+                .wander_target
+                .get_or_insert_with(|| position + Vec2::new_random_signed(&100.0, &250.0)),
+        };
+
+        let to_destination = destination - position;
+
+        let mut drive = false;
+        let mut turn = 0.0;
+
+        if to_destination.length() > ai.stop_distance {
+            let forward = (transform.rotation * Vec3::Y).truncate();
+
+            turn = forward.angle_between(to_destination);
+
+            drive = turn.abs() < DRIVE_CONE_RAD;
+        } else if perception.target.is_none() {
+            ai.wander_target = None;
+        }
+
+        set_action(&mut action_state, PlayerAction::MoveForward, drive);
+        set_action(&mut action_state, PlayerAction::MoveBackwards, false);
+        set_action(&mut action_state, PlayerAction::TurnLeft, turn > TURN_TOLERANCE_RAD);
+        set_action(&mut action_state, PlayerAction::TurnRight, turn < -TURN_TOLERANCE_RAD);
+
+        for child in children.iter() {
+            let (mut aim, weapon, mut turret_action_state) = match turrets.get_mut(*child) {
+                Ok(turret) => turret,
+                Err(_) => continue,
+            };
+
+            aim.target = perception.target_position();
+
+            let in_range = perception
+                .target
+                .as_ref()
+                .map(|target| target.visible && target.position.distance(position) <= ai.fire_range)
+                .unwrap_or(false);
+
+            // release while reloading so the next shot is a fresh press, just like clicking
+            set_action(
+                &mut turret_action_state,
+                PlayerAction::FireCannon,
+                in_range && weapon.is_ready(),
+            );
+        }
+    }
+}
+
+/// Turns AI turrets towards their aim as fast as the player's turret turns towards the cursor
+#[allow(clippy::type_complexity)]
+pub fn rotate_turrets_to_aim(
+    mut turrets: Query<(&mut Transform, &GlobalTransform, &TurretAim, &Movable)>,
+    time: Res<Time>,
+) {
+    for (mut transform, global_transform, aim, movable) in turrets.iter_mut() {
+        if let Some(target) = aim.target {
+            let angle = get_angle_from_transform(&global_transform.compute_transform(), &target);
+
+            let max_turn = movable.rotation_speed_rad * time.delta_seconds();
+
+            transform.rotate_z(angle.clamp(-max_turn, max_turn));
+        }
+    }
+}
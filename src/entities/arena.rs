@@ -0,0 +1,401 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use super::{
+    enemy::EnemyArchetype,
+    level::{LevelMapDef, PropPlacementDef, SpawnPointDef},
+    prop::PropKind,
+    spawn_points::{SpawnPointActivation, SpawnTableEntry},
+};
+
+/// Tiles between two waypoints of a generated enemy path
+const PATH_WAYPOINT_SPACING: usize = 4;
+
+/// Tiles kept free of obstacles around the headquarters, the player spawn and the spawn points
+const CLEARING_RADIUS: i32 = 2;
+
+const MIN_ARENA_SIZE: i32 = 15;
+
+/// Share of the obstacle density placed as destructible props on the grass left after carving roads
+const PROP_DENSITY_SHARE: f32 = 0.25;
+
+/// Props that fit into a single tile, so they never block more than the tile they stand on
+const ARENA_PROPS: [PropKind; 3] = [PropKind::Tree, PropKind::Crate, PropKind::Fence];
+
+#[derive(Clone, Copy, Debug)]
+pub struct ArenaParams {
+    pub width: i32,
+    pub height: i32,
+    /// Share of the free tiles turned into rocks, before paths are carved through them
+    pub obstacle_density: f32,
+    pub spawn_points: u32,
+}
+
+impl Default for ArenaParams {
+    fn default() -> Self {
+        Self {
+            width: 31,
+            height: 25,
+            obstacle_density: 0.2,
+            spawn_points: 3,
+        }
+    }
+}
+
+/// Side of the arena a spawn point is placed on, spawn points take turns going around
+#[derive(Clone, Copy)]
+enum Side {
+    North,
+    East,
+    West,
+}
+
+impl Side {
+    fn name(&self) -> &'static str {
+        match self {
+            Side::North => "north",
+            Side::East => "east",
+            Side::West => "west",
+        }
+    }
+}
+
+fn neighbours(tile: IVec2) -> [IVec2; 4] {
+    [
+        tile + IVec2::X,
+        tile + IVec2::NEG_X,
+        tile + IVec2::Y,
+        tile + IVec2::NEG_Y,
+    ]
+}
+
+struct ArenaGrid {
+    width: i32,
+    height: i32,
+    symbols: Vec<char>,
+}
+
+impl ArenaGrid {
+    fn new(width: i32, height: i32) -> Self {
+        let mut grid = ArenaGrid {
+            width,
+            height,
+            symbols: vec!['.'; (width * height) as usize],
+        };
+
+        for x in 0..width {
+            grid.set(IVec2::new(x, 0), '#');
+            grid.set(IVec2::new(x, height - 1), '#');
+        }
+
+        for y in 0..height {
+            grid.set(IVec2::new(0, y), '#');
+            grid.set(IVec2::new(width - 1, y), '#');
+        }
+
+        return grid;
+    }
+
+    fn is_interior(&self, tile: IVec2) -> bool {
+        tile.x > 0 && tile.y > 0 && tile.x < self.width - 1 && tile.y < self.height - 1
+    }
+
+    fn get(&self, tile: IVec2) -> char {
+        self.symbols[(tile.y * self.width + tile.x) as usize]
+    }
+
+    fn set(&mut self, tile: IVec2, symbol: char) {
+        self.symbols[(tile.y * self.width + tile.x) as usize] = symbol;
+    }
+
+    /// Cheapest way from `from` to `to` through the interior, digging through rocks is expensive but allowed
+    fn dig_path(&self, from: IVec2, to: IVec2) -> Vec<IVec2> {
+        let cost = |tile: IVec2| if self.get(tile) == '#' { 10 } else { 1 };
+
+        let mut best: HashMap<IVec2, u32> = HashMap::from([(from, 0)]);
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+        let mut open = VecDeque::from([from]);
+
+        // costs are small so a queue that revisits improved tiles settles quickly
+        while let Some(current) = open.pop_front() {
+            for next in neighbours(current) {
+                if !self.is_interior(next) {
+                    continue;
+                }
+
+                let next_cost = best[&current] + cost(next);
+
+                if best.get(&next).map_or(true, |known| next_cost < *known) {
+                    best.insert(next, next_cost);
+                    came_from.insert(next, current);
+                    open.push_back(next);
+                }
+            }
+        }
+
+        let mut path = vec![to];
+        let mut tile = to;
+
+        while let Some(previous) = came_from.get(&tile) {
+            tile = *previous;
+            path.push(tile);
+        }
+
+        path.reverse();
+
+        return path;
+    }
+
+    fn rows(&self) -> Vec<String> {
+        self.symbols
+            .chunks(self.width as usize)
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+}
+
+fn spawn_table(index: usize) -> (Vec<SpawnTableEntry>, SpawnPointActivation) {
+    let entry = |archetype, weight| SpawnTableEntry { archetype, weight };
+
+    match index {
+        0 => (vec![entry(EnemyArchetype::Grunt, 3)], SpawnPointActivation::Always),
+        1 => (
+            vec![entry(EnemyArchetype::Grunt, 3), entry(EnemyArchetype::Scout, 2)],
+            SpawnPointActivation::FromWave(2),
+        ),
+        _ => (
+            vec![
+                entry(EnemyArchetype::Grunt, 3),
+                entry(EnemyArchetype::Heavy, 1),
+                entry(EnemyArchetype::Tank, 1),
+            ],
+            SpawnPointActivation::FromWave(3),
+        ),
+    }
+}
+
+/// Generates an arena in the map file layout, so it goes through the same validation and spawning as
+/// hand-made maps. The same seed and parameters always give the same arena.
+pub fn generate_arena(seed: u64, params: &ArenaParams) -> LevelMapDef {
+    let rng = fastrand::Rng::with_seed(seed);
+
+    let width = params.width.max(MIN_ARENA_SIZE);
+    let height = params.height.max(MIN_ARENA_SIZE);
+
+    let mut grid = ArenaGrid::new(width, height);
+
+    let headquarters = IVec2::new(width / 2, height - 4);
+    let player_spawn = IVec2::new(width / 2, height / 2);
+
+    let sides = [Side::North, Side::East, Side::West];
+
+    let mut spawn_tiles = Vec::new();
+    let mut names_used: HashMap<&str, u32> = HashMap::new();
+
+    for index in 0..params.spawn_points as usize {
+        let side = sides[index % sides.len()];
+
+        let tile = match side {
+            Side::North => IVec2::new(rng.i32(3..width - 3), 2),
+            Side::East => IVec2::new(width - 3, rng.i32(2..height / 2)),
+            Side::West => IVec2::new(2, rng.i32(2..height / 2)),
+        };
+
+        let count = names_used.entry(side.name()).or_insert(0);
+
+        *count += 1;
+
+        let name = match *count {
+            1 => side.name().to_string(),
+            count => format!("{} {}", side.name(), count),
+        };
+
+        spawn_tiles.push((name, tile));
+    }
+
+    let mut clearings = vec![headquarters, player_spawn];
+
+    clearings.extend(spawn_tiles.iter().map(|(_, tile)| *tile));
+
+    let in_clearing = |tile: IVec2| {
+        clearings.iter().any(|clearing| {
+            let delta = (tile - *clearing).abs();
+
+            delta.x <= CLEARING_RADIUS && delta.y <= CLEARING_RADIUS
+        })
+    };
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let tile = IVec2::new(x, y);
+
+            if !in_clearing(tile) && rng.f32() < params.obstacle_density {
+                grid.set(tile, '#');
+            }
+        }
+    }
+
+    // roads from every spawn point and the player spawn to the headquarters guarantee connectivity
+    let mut roads: HashSet<IVec2> = HashSet::new();
+    let mut spawn_points = Vec::new();
+
+    for (index, (name, tile)) in spawn_tiles.into_iter().enumerate() {
+        let path = grid.dig_path(tile, headquarters);
+
+        roads.extend(path.iter().copied());
+
+        let waypoints = path
+            .iter()
+            .skip(PATH_WAYPOINT_SPACING)
+            .step_by(PATH_WAYPOINT_SPACING)
+            .map(|waypoint| (waypoint.x, waypoint.y))
+            .collect();
+
+        let (spawn_table, activation) = spawn_table(index);
+
+        spawn_points.push(SpawnPointDef {
+            name,
+            tile: (tile.x, tile.y),
+            spawn_table,
+            cooldown_sec: 2.0 + index as f32 * 0.5,
+            max_alive: 4usize.saturating_sub(index / 2).max(2),
+            activation,
+            path: waypoints,
+        });
+    }
+
+    roads.extend(grid.dig_path(player_spawn, headquarters));
+
+    for road in roads {
+        grid.set(road, '=');
+    }
+
+    // props only go onto grass off the roads, so they never cut a spawn point off the headquarters
+    let mut props = Vec::new();
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let tile = IVec2::new(x, y);
+
+            if grid.get(tile) != '.'
+                || in_clearing(tile)
+                || rng.f32() >= params.obstacle_density * PROP_DENSITY_SHARE
+            {
+                continue;
+            }
+
+            props.push(PropPlacementDef {
+                kind: ARENA_PROPS[rng.usize(..ARENA_PROPS.len())],
+                tile: (x, y),
+            });
+        }
+    }
+
+    grid.set(player_spawn, 'P');
+    grid.set(headquarters, 'H');
+
+    LevelMapDef {
+        name: format!("Arena {}", seed),
+        tiles: grid.rows(),
+        spawn_points,
+        towers: Vec::new(),
+        props,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{level::LevelMap, navigation::NavGrid};
+
+    const SEEDS: [u64; 5] = [0, 1, 7, 42, 1234];
+
+    /// Navigation grid of the map with rocks and props blocked, as it is after spawning the level
+    fn nav_grid_for(map: &LevelMap) -> NavGrid {
+        let mut nav_grid = NavGrid {
+            min_cell: map.cell(IVec2::new(0, map.height - 1)),
+            size: IVec2::new(map.width, map.height),
+            ..Default::default()
+        };
+
+        for (tile, terrain) in map.tiles() {
+            nav_grid.set_terrain(map.cell(tile), terrain);
+        }
+
+        let rocks = map
+            .tiles()
+            .filter(|(_, terrain)| terrain.is_blocking())
+            .map(|(tile, _)| map.cell(tile));
+
+        let props = map
+            .props
+            .iter()
+            .map(|prop| map.cell(IVec2::from(prop.tile)));
+
+        nav_grid.set_blocked(rocks.chain(props));
+
+        return nav_grid;
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_arena() {
+        let params = ArenaParams::default();
+
+        for seed in SEEDS {
+            assert_eq!(
+                generate_arena(seed, &params).tiles,
+                generate_arena(seed, &params).tiles
+            );
+        }
+    }
+
+    #[test]
+    fn generated_arenas_are_valid_maps() {
+        for obstacle_density in [0.0, 0.2, 0.4] {
+            let params = ArenaParams {
+                obstacle_density,
+                ..Default::default()
+            };
+
+            for seed in SEEDS {
+                let result = LevelMap::from_definition(generate_arena(seed, &params));
+
+                assert!(
+                    result.is_ok(),
+                    "seed {} density {}: {:?}",
+                    seed,
+                    obstacle_density,
+                    result.err()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_spawn_point_has_a_path_to_the_headquarters() {
+        let params = ArenaParams {
+            obstacle_density: 0.4,
+            spawn_points: 5,
+            ..Default::default()
+        };
+
+        for seed in SEEDS {
+            let map = LevelMap::from_definition(generate_arena(seed, &params)).unwrap();
+            let nav_grid = nav_grid_for(&map);
+
+            let headquarters = map.tile_position(map.headquarters, &nav_grid);
+
+            for spawn_point in map.spawn_points.iter() {
+                let from = map.tile_position(IVec2::from(spawn_point.tile), &nav_grid);
+
+                assert!(
+                    nav_grid.find_path(from, headquarters).is_some(),
+                    "seed {}: {} is cut off",
+                    seed,
+                    spawn_point.name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::{math::Mat2, prelude::*};
+use bevy_rand_utils::RandomFromRange;
+
+use super::{
+    ai::perception::Perception,
+    bounds::WorldBounds,
+    difficulty::{Difficulty, DifficultyModifiers},
+    economy::Bounty,
+    hud::UiFont,
+    layers::ZLayers,
+    enemy::{Enemy, EnemyArchetype},
+    loot::{LootDropper, BOSS_LOOT_TABLE},
+    projectiles::rotation_facing,
+    score::ScoreValue,
+    shared::{CollisionMask, DisplayName, EntitySharedBundle, Health, Lifetime, Movable},
+    spawner::spawn_enemy,
+    sprites::{SpriteDefinitions, BOSS_PROJECTILE_SPRITE, JUGGERNAUT_SPRITE},
+    terrain::TerrainEffect,
+    weapons::{spawn_weapon_projectile, Weapon},
+};
+
+#[derive(Clone, Copy)]
+pub enum BossMovement {
+    Hold,
+    Orbit { radius: f32 },
+    Charge,
+}
+
+#[derive(Clone, Copy)]
+pub enum WeaponPattern {
+    Aimed,
+    Spread { projectiles: u32, arc_rad: f32 },
+    Radial { projectiles: u32 },
+}
+
+impl WeaponPattern {
+    pub fn directions(&self, aim: Vec2) -> Vec<Vec2> {
+        match *self {
+            WeaponPattern::Aimed => vec![aim],
+            WeaponPattern::Spread {
+                projectiles,
+                arc_rad,
+            } => {
+                if projectiles < 2 {
+                    return vec![aim];
+                }
+
+                let step = arc_rad / (projectiles - 1) as f32;
+
+                (0..projectiles)
+                    .map(|i| Mat2::from_angle(-arc_rad / 2.0 + step * i as f32) * aim)
+                    .collect()
+            }
+            WeaponPattern::Radial { projectiles } => (0..projectiles)
+                .map(|i| Mat2::from_angle(TAU * i as f32 / projectiles as f32) * aim)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MinionSpawn {
+    pub interval_sec: f32,
+    pub count: u32,
+    pub max_alive: usize,
+}
+
+#[derive(Clone)]
+pub struct BossPhase {
+    pub name: String,
+    /// The phase starts once health drops to this fraction of max health. Phases are ordered by descending threshold.
+    pub health_threshold: f32,
+    pub movement: BossMovement,
+    pub speed: i32,
+    pub weapon_pattern: WeaponPattern,
+    pub fire_interval_sec: f32,
+    pub minions: Option<MinionSpawn>,
+}
+
+#[derive(Clone)]
+pub struct BossDefinition {
+    pub name: String,
+    pub health: u16,
+    /// Sprite definition path, sizes the collider as well
+    pub sprite: &'static str,
+    pub projectile_damage: u16,
+    pub bounty: u32,
+    pub score: u32,
+    pub phases: Vec<BossPhase>,
+}
+
+impl BossDefinition {
+    pub fn juggernaut() -> Self {
+        BossDefinition {
+            name: "Juggernaut".to_string(),
+            health: 1000,
+            sprite: JUGGERNAUT_SPRITE,
+            projectile_damage: 20,
+            bounty: 250,
+            score: 5000,
+            phases: vec![
+                BossPhase {
+                    name: "Siege".to_string(),
+                    health_threshold: 1.0,
+                    movement: BossMovement::Orbit { radius: 300.0 },
+                    speed: 40,
+                    weapon_pattern: WeaponPattern::Aimed,
+                    fire_interval_sec: 1.2,
+                    minions: None,
+                },
+                BossPhase {
+                    name: "Barrage".to_string(),
+                    health_threshold: 0.6,
+                    movement: BossMovement::Orbit { radius: 220.0 },
+                    speed: 60,
+                    weapon_pattern: WeaponPattern::Spread {
+                        projectiles: 5,
+                        arc_rad: PI / 3.0,
+                    },
+                    fire_interval_sec: 1.5,
+                    minions: Some(MinionSpawn {
+                        interval_sec: 8.0,
+                        count: 2,
+                        max_alive: 4,
+                    }),
+                },
+                BossPhase {
+                    name: "Berserk".to_string(),
+                    health_threshold: 0.3,
+                    movement: BossMovement::Charge,
+                    speed: 90,
+                    weapon_pattern: WeaponPattern::Radial { projectiles: 12 },
+                    fire_interval_sec: 2.0,
+                    minions: Some(MinionSpawn {
+                        interval_sec: 5.0,
+                        count: 3,
+                        max_alive: 6,
+                    }),
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Boss {
+    pub phases: Vec<BossPhase>,
+    pub current_phase: usize,
+    /// Difficulty speed modifier applied to every phase speed
+    pub speed_modifier: f32,
+    minion_timer: Timer,
+    orbit_angle: f32,
+}
+
+impl Boss {
+    pub fn new(phases: Vec<BossPhase>, speed_modifier: f32) -> Self {
+        let mut boss = Boss {
+            phases,
+            current_phase: 0,
+            speed_modifier,
+            minion_timer: Timer::default(),
+            orbit_angle: 0.0,
+        };
+
+        boss.enter_phase(0);
+
+        boss
+    }
+
+    pub fn phase(&self) -> &BossPhase {
+        &self.phases[self.current_phase]
+    }
+
+    fn enter_phase(&mut self, phase: usize) {
+        self.current_phase = phase;
+
+        let minions = self.phase().minions;
+
+        if let Some(minions) = minions {
+            self.minion_timer = Timer::from_seconds(minions.interval_sec, true);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Minion {
+    pub boss: Entity,
+}
+
+pub struct BossSpawned {
+    pub boss: Entity,
+    pub name: String,
+}
+
+#[derive(Component)]
+pub struct BossHealthBar {
+    pub boss: Entity,
+}
+
+#[derive(Component, Default)]
+pub struct BossHealthBarFill;
+
+pub fn spawn_boss(
+    definition: &BossDefinition,
+    position: Vec2,
+    modifiers: &DifficultyModifiers,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let sprite = sprites.get(definition.sprite);
+
+    let mut shared = EntitySharedBundle::from_definition(&definition.name, sprite);
+
+    shared.sprite.transform = Transform::from_translation(position.extend(layers.units));
+
+    let first_phase = &definition.phases[0];
+
+    let mut weapon = Weapon::new(
+        first_phase.fire_interval_sec,
+        modifiers.scale_damage(definition.projectile_damage),
+        140.0,
+        vec![CollisionMask::PLAYER],
+    );
+
+    weapon.projectile_sprite = BOSS_PROJECTILE_SPRITE;
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Enemy)
+        .insert(Boss::new(definition.phases.clone(), modifiers.speed))
+        .insert(Movable {
+            speed: modifiers.scale_speed(first_phase.speed),
+            ..Default::default()
+        })
+        .insert(weapon)
+        .insert(Perception::new(700.0, TAU))
+        .insert(sprite.collider(vec![CollisionMask::ENEMY]))
+        .insert(Health::new(modifiers.scale_health(definition.health)))
+        .insert(Bounty(definition.bounty))
+        .insert(ScoreValue(definition.score))
+        .insert(LootDropper::new(BOSS_LOOT_TABLE))
+        .id()
+}
+
+#[allow(clippy::type_complexity)]
+pub fn advance_boss_phases(
+    mut bosses: Query<(
+        &DisplayName,
+        &Health,
+        &mut Boss,
+        &mut Weapon,
+        &mut Movable,
+        Option<&mut TerrainEffect>,
+    )>,
+) {
+    for (name, health, mut boss, mut weapon, mut movable, terrain_effect) in bosses.iter_mut() {
+        let health_fraction = health.current_health as f32 / health.max_health as f32;
+
+        let reached_phase = boss
+            .phases
+            .iter()
+            .rposition(|phase| health_fraction <= phase.health_threshold)
+            .unwrap_or(0);
+
+        if reached_phase <= boss.current_phase {
+            continue;
+        }
+
+        boss.enter_phase(reached_phase);
+
+        let phase = boss.phase();
+
+        weapon.set_cooldown(phase.fire_interval_sec);
+        let speed = (phase.speed as f32 * boss.speed_modifier).round() as i32;
+
+        // on slow ground the terrain keeps scaling the new phase speed
+        match terrain_effect {
+            Some(mut effect) => {
+                let rotation_speed_rad = effect.base_rotation_speed_rad;
+
+                effect.rebase(speed, rotation_speed_rad, &mut movable);
+            }
+            None => movable.speed = speed,
+        }
+
+        info!("Boss {} enters phase {}", name.0, phase.name);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn move_bosses(
+    mut bosses: Query<(&mut Transform, &mut Boss, &Perception, &Movable)>,
+    time: Res<Time>,
+) {
+    for (mut transform, mut boss, perception, movable) in bosses.iter_mut() {
+        let target_position = match perception.target_position() {
+            Some(target_position) => target_position,
+            None => continue,
+        };
+
+        let movement = boss.phase().movement;
+
+        let destination = match movement {
+            BossMovement::Hold => continue,
+            BossMovement::Orbit { radius } => {
+                boss.orbit_angle += movable.speed as f32 / radius * time.delta_seconds();
+
+                target_position + Vec2::new(boss.orbit_angle.cos(), boss.orbit_angle.sin()) * radius
+            }
+            BossMovement::Charge => target_position,
+        };
+
+        let to_destination = destination - transform.translation.truncate();
+
+        let step = movable.speed as f32 * time.delta_seconds();
+
+        if to_destination.length() > step {
+            transform.translation += (to_destination.normalize() * step).extend(0.0);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn fire_boss_weapon_patterns(
+    mut bosses: Query<(&GlobalTransform, &Boss, &Perception, &mut Weapon)>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    for (global_transform, boss, perception, mut weapon) in bosses.iter_mut() {
+        let target_position = match perception.target_position() {
+            Some(target_position) => target_position,
+            None => continue,
+        };
+
+        if !weapon.try_fire() {
+            continue;
+        }
+
+        let boss_position = global_transform.translation().truncate();
+
+        let aim = (target_position - boss_position).normalize_or_zero();
+
+        for direction in boss.phase().weapon_pattern.directions(aim) {
+            spawn_weapon_projectile(
+                &mut commands,
+                &layers,
+                &sprites,
+                &weapon,
+                boss_position,
+                rotation_facing(direction),
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_boss_minions(
+    mut bosses: Query<(Entity, &Transform, &mut Boss)>,
+    minions: Query<&Minion>,
+    difficulty: Res<Difficulty>,
+    bounds: Res<WorldBounds>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let modifiers = difficulty.modifiers();
+
+    for (boss_entity, transform, mut boss) in bosses.iter_mut() {
+        let minion_spawn = match boss.phase().minions {
+            Some(minion_spawn) => minion_spawn,
+            None => continue,
+        };
+
+        if !boss.minion_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let alive = minions.iter().filter(|minion| minion.boss == boss_entity).count();
+
+        let spawn_count = (minion_spawn.count as usize).min(minion_spawn.max_alive.saturating_sub(alive));
+
+        for _ in 0..spawn_count {
+            let offset = Vec2::new_random_signed(&60.0, &120.0);
+
+            let minion = spawn_enemy(
+                EnemyArchetype::Grunt,
+                bounds.clamp_spawn(transform.translation.truncate() + offset),
+                &modifiers,
+                &layers,
+                &sprites,
+                &mut commands,
+            );
+
+            commands.entity(minion).insert(Minion { boss: boss_entity });
+        }
+    }
+}
+
+pub fn announce_boss_spawns(
+    bosses: Query<(Entity, &DisplayName), Added<Boss>>,
+    mut boss_spawned: EventWriter<BossSpawned>,
+    font: Res<UiFont>,
+    mut commands: Commands,
+) {
+    for (boss, name) in bosses.iter() {
+        info!("Boss {} has entered the battlefield!", name.0);
+
+        boss_spawned.send(BossSpawned {
+            boss,
+            name: name.0.clone(),
+        });
+
+        spawn_boss_warning_banner(&name.0, &font, &mut commands);
+        spawn_boss_health_bar(boss, &mut commands);
+    }
+}
+
+fn spawn_boss_warning_banner(name: &str, font: &UiFont, commands: &mut Commands) {
+    let text = commands
+        .spawn_bundle(TextBundle::from_section(
+            format!("{} approaches!", name),
+            font.style(32.0, Color::WHITE),
+        ))
+        .id();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(48.0)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.0),
+                    top: Val::Px(48.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: UiColor(Color::rgba(0.8, 0.0, 0.0, 0.6)),
+            ..Default::default()
+        })
+        .insert(Lifetime::new(3.0))
+        .add_child(text);
+}
+
+fn spawn_boss_health_bar(boss: Entity, commands: &mut Commands) {
+    let fill = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            color: UiColor(Color::rgb(0.8, 0.1, 0.1)),
+            ..Default::default()
+        })
+        .insert(BossHealthBarFill)
+        .id();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Px(20.0)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(25.0),
+                    top: Val::Px(16.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::rgb(0.15, 0.15, 0.15)),
+            ..Default::default()
+        })
+        .insert(BossHealthBar { boss })
+        .add_child(fill);
+}
+
+pub fn update_boss_health_bars(
+    health_bars: Query<(Entity, &BossHealthBar, &Children)>,
+    mut fills: Query<&mut Style, With<BossHealthBarFill>>,
+    bosses: Query<&Health, With<Boss>>,
+    mut commands: Commands,
+) {
+    for (health_bar_entity, health_bar, children) in health_bars.iter() {
+        if let Ok(health) = bosses.get(health_bar.boss) {
+            let health_fraction = health.current_health as f32 / health.max_health as f32;
+
+            for child in children.iter() {
+                if let Ok(mut style) = fills.get_mut(*child) {
+                    style.size.width = Val::Percent(health_fraction * 100.0);
+                }
+            }
+        } else {
+            commands.entity(health_bar_entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use super::{
+    projectiles::Projectile,
+    shared::{Collider, Movable, Solid},
+};
+
+/// Room kept to the edge when spawning, so a spawned tank does not stick out of the world
+const SPAWN_CLEARANCE: f32 = 64.0;
+
+/// Playable area of the world, nothing moves or spawns outside of it
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            min: Vec2::new(-1024.0, -768.0),
+            max: Vec2::new(1024.0, 768.0),
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        WorldBounds {
+            min: min.min(max),
+            max: max.max(min),
+        }
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+
+    /// Whether a box of `size` centered on `position` lies completely inside the bounds
+    pub fn contains_box(&self, position: Vec2, size: Vec2) -> bool {
+        let half_size = size / 2.0;
+
+        self.contains(position - half_size) && self.contains(position + half_size)
+    }
+
+    /// Closest position to `position` at which a box of `size` lies inside the bounds
+    pub fn clamp(&self, position: Vec2, size: Vec2) -> Vec2 {
+        let half_size = (size / 2.0).min((self.max - self.min) / 2.0);
+
+        position.clamp(self.min + half_size, self.max - half_size)
+    }
+
+    /// Moves a spawn position inside the bounds
+    pub fn clamp_spawn(&self, position: Vec2) -> Vec2 {
+        self.clamp(position, Vec2::splat(SPAWN_CLEARANCE))
+    }
+}
+
+/// Stops tanks and enemies at the edge of the world, like they would at a solid
+#[allow(clippy::type_complexity)]
+pub fn keep_movers_in_bounds(
+    mut movers: Query<
+        (&mut Transform, &Collider),
+        (With<Movable>, Without<Solid>, Without<Parent>),
+    >,
+    bounds: Res<WorldBounds>,
+) {
+    for (mut transform, collider) in movers.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let clamped = bounds.clamp(position, collider.size);
+
+        if clamped != position {
+            transform.translation = clamped.extend(transform.translation.z);
+        }
+    }
+}
+
+pub fn despawn_projectiles_out_of_bounds(
+    projectiles: Query<(Entity, &Transform), With<Projectile>>,
+    bounds: Res<WorldBounds>,
+    mut commands: Commands,
+) {
+    for (projectile, transform) in projectiles.iter() {
+        if !bounds.contains(transform.translation.truncate()) {
+            commands.entity(projectile).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds() -> WorldBounds {
+        WorldBounds::new(Vec2::new(-100.0, -50.0), Vec2::new(100.0, 50.0))
+    }
+
+    #[test]
+    fn new_orders_corners() {
+        let bounds = WorldBounds::new(Vec2::new(100.0, -50.0), Vec2::new(-100.0, 50.0));
+
+        assert_eq!(bounds.min, Vec2::new(-100.0, -50.0));
+        assert_eq!(bounds.max, Vec2::new(100.0, 50.0));
+    }
+
+    #[test]
+    fn clamp_keeps_positions_inside() {
+        let position = Vec2::new(10.0, -20.0);
+
+        assert_eq!(bounds().clamp(position, Vec2::splat(20.0)), position);
+    }
+
+    #[test]
+    fn clamp_keeps_the_whole_box_inside() {
+        let size = Vec2::new(20.0, 10.0);
+
+        assert_eq!(
+            bounds().clamp(Vec2::new(200.0, -80.0), size),
+            Vec2::new(90.0, -45.0)
+        );
+        assert!(bounds().contains_box(bounds().clamp(Vec2::new(-95.0, 48.0), size), size));
+    }
+
+    #[test]
+    fn clamp_centers_boxes_larger_than_the_bounds() {
+        let clamped = bounds().clamp(Vec2::new(300.0, 300.0), Vec2::splat(500.0));
+
+        assert_eq!(clamped, Vec2::ZERO);
+    }
+}
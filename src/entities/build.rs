@@ -0,0 +1,388 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use bevy_mouse_position_component::MousePosition2d;
+use leafwing_input_manager::prelude::*;
+
+use super::{
+    bounds::WorldBounds,
+    economy::{Bank, TransactionReason, Wallet},
+    hq::Headquarters,
+    layers::ZLayers,
+    navigation::NavGrid,
+    player::{PlayerAction, PlayerControlled},
+    projectiles::Projectile,
+    shared::Collider,
+    spawn_points::SpawnPoint,
+    sprites::{SpriteDefinitions, BUILD_GHOST_SPRITE},
+    tower::{PlaceTower, TowerDefinitions, TowerKind},
+    walls::{PlaceWall, WALL_COST},
+};
+
+/// Distance from the player tank within which structures can be placed
+const BUILD_RANGE: f32 = 350.0;
+
+/// Share of the cost paid back when selling a structure
+const SELL_REFUND: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlacementError {
+    Overlaps,
+    BlocksPath,
+    OutOfBounds,
+    OutOfRange,
+    Unaffordable,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StructureKind {
+    Tower(TowerKind),
+    Wall,
+}
+
+impl StructureKind {
+    fn next(&self) -> Self {
+        match self {
+            StructureKind::Tower(TowerKind::Gun) => StructureKind::Tower(TowerKind::Missile),
+            StructureKind::Tower(TowerKind::Missile) => StructureKind::Tower(TowerKind::Slow),
+            StructureKind::Tower(TowerKind::Slow) => StructureKind::Wall,
+            StructureKind::Wall => StructureKind::Tower(TowerKind::Gun),
+        }
+    }
+}
+
+/// Placed structure that can be sold or relocated in build mode
+#[derive(Component)]
+pub struct Structure {
+    pub kind: StructureKind,
+    /// Price paid for the structure, selling refunds part of it
+    pub cost: u32,
+}
+
+pub struct BuildMode {
+    pub active: bool,
+    pub selected: StructureKind,
+    /// Placed structure that is being moved, the next placement relocates it instead of buying a new one
+    pub relocating: Option<Entity>,
+    /// Snapped position under the cursor and whether a structure can be placed there
+    pub placement: Option<(Vec2, Result<(), PlacementError>)>,
+}
+
+impl Default for BuildMode {
+    fn default() -> Self {
+        Self {
+            active: false,
+            selected: StructureKind::Tower(TowerKind::Gun),
+            relocating: None,
+            placement: None,
+        }
+    }
+}
+
+/// Translucent preview of the selected structure at the snapped cursor position
+#[derive(Component)]
+pub struct BuildGhost;
+
+const GHOST_VALID_COLOR: Color = Color::rgba(0.0, 1.0, 0.0, 0.4);
+const GHOST_INVALID_COLOR: Color = Color::rgba(1.0, 0.0, 0.0, 0.4);
+
+/// Spawned before the sprite definitions load, `update_build_ghost` sizes it once they are
+pub fn spawn_build_ghost(layers: Res<ZLayers>, mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: GHOST_VALID_COLOR,
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, layers.ui),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(BuildGhost);
+}
+
+pub fn handle_build_mode_input(
+    players: Query<&ActionState<PlayerAction>, With<PlayerControlled>>,
+    mut build_mode: ResMut<BuildMode>,
+) {
+    let action_state = match players.get_single() {
+        Ok(action_state) => action_state,
+        Err(_) => return,
+    };
+
+    if action_state.just_pressed(PlayerAction::ToggleBuildMode) {
+        build_mode.active = !build_mode.active;
+        build_mode.relocating = None;
+
+        info!("Build mode {}", if build_mode.active { "enabled" } else { "disabled" });
+    }
+
+    if build_mode.active && action_state.just_pressed(PlayerAction::CycleBuildSelection) {
+        build_mode.selected = build_mode.selected.next();
+        build_mode.relocating = None;
+
+        info!("Selected {:?} for building", build_mode.selected);
+    }
+}
+
+fn overlaps_collider(
+    position: Vec2,
+    size: Vec2,
+    relocating: Option<Entity>,
+    colliders: &Query<(Entity, &GlobalTransform, &Collider), Without<Projectile>>,
+) -> bool {
+    colliders
+        .iter()
+        .filter(|(entity, _, _)| Some(*entity) != relocating)
+        .any(|(_, collider_transform, collider)| {
+            collide(
+                position.extend(0.0),
+                size,
+                collider_transform.translation(),
+                collider.size,
+            )
+            .is_some()
+        })
+}
+
+/// Whether occupying the cells at `position` leaves any spawn point without a path to the headquarters
+fn cuts_off_spawn_points(
+    position: Vec2,
+    size: Vec2,
+    relocating: Option<Entity>,
+    nav_grid: &NavGrid,
+    colliders: &Query<(Entity, &GlobalTransform, &Collider), Without<Projectile>>,
+    spawn_points: &Query<&GlobalTransform, With<SpawnPoint>>,
+    headquarters: &Query<&GlobalTransform, With<Headquarters>>,
+) -> bool {
+    let hq_position = match headquarters.get_single() {
+        Ok(hq_transform) => hq_transform.translation().truncate(),
+        Err(_) => return false,
+    };
+
+    let placed_cells = nav_grid.cells_covered(position, size);
+
+    let vacated_cells = relocating
+        .and_then(|entity| colliders.get(entity).ok())
+        .map(|(_, transform, collider)| {
+            nav_grid.cells_covered(transform.translation().truncate(), collider.size)
+        })
+        .unwrap_or_default();
+
+    let passable = |cell: IVec2| {
+        let blocked = nav_grid.is_blocked(cell) && !vacated_cells.contains(&cell);
+
+        !blocked && !placed_cells.contains(&cell)
+    };
+
+    spawn_points.iter().any(|spawn_point_transform| {
+        let spawn_point_position = spawn_point_transform.translation().truncate();
+
+        nav_grid
+            .find_path_where(spawn_point_position, hq_position, &passable)
+            .is_none()
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn validate_build_placement(
+    mut build_mode: ResMut<BuildMode>,
+    mouse_positions: Query<&MousePosition2d>,
+    nav_grid: Res<NavGrid>,
+    bounds: Res<WorldBounds>,
+    players: Query<&GlobalTransform, With<PlayerControlled>>,
+    colliders: Query<(Entity, &GlobalTransform, &Collider), Without<Projectile>>,
+    spawn_points: Query<&GlobalTransform, With<SpawnPoint>>,
+    headquarters: Query<&GlobalTransform, With<Headquarters>>,
+    definitions: Res<TowerDefinitions>,
+    wallet: Res<Wallet>,
+) {
+    if !build_mode.active {
+        build_mode.placement = None;
+
+        return;
+    }
+
+    let position = match mouse_positions.get_single() {
+        Ok(mouse_position) => nav_grid.snap(mouse_position.world_pos),
+        Err(_) => return,
+    };
+
+    let size = Vec2::splat(nav_grid.cell_size);
+
+    let relocating = build_mode.relocating;
+
+    let in_range = players
+        .get_single()
+        .map(|player_transform| {
+            player_transform.translation().truncate().distance(position) <= BUILD_RANGE
+        })
+        .unwrap_or(false);
+
+    let cost = match (relocating, build_mode.selected) {
+        (Some(_), _) => 0,
+        (None, StructureKind::Tower(kind)) => definitions
+            .get(kind)
+            .map(|definition| definition.cost)
+            .unwrap_or(u32::MAX),
+        (None, StructureKind::Wall) => WALL_COST,
+    };
+
+    let validity = if !bounds.contains_box(position, size) {
+        Err(PlacementError::OutOfBounds)
+    } else if !in_range {
+        Err(PlacementError::OutOfRange)
+    } else if wallet.balance < cost {
+        Err(PlacementError::Unaffordable)
+    } else if overlaps_collider(position, size, relocating, &colliders) {
+        Err(PlacementError::Overlaps)
+    } else if cuts_off_spawn_points(
+        position,
+        size,
+        relocating,
+        &nav_grid,
+        &colliders,
+        &spawn_points,
+        &headquarters,
+    ) {
+        Err(PlacementError::BlocksPath)
+    } else {
+        Ok(())
+    };
+
+    build_mode.placement = Some((position, validity));
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_build_ghost(
+    build_mode: Res<BuildMode>,
+    mut ghosts: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<BuildGhost>>,
+    sprites: Res<SpriteDefinitions>,
+) {
+    let size = sprites.get(BUILD_GHOST_SPRITE).size();
+
+    for (mut transform, mut sprite, mut visibility) in ghosts.iter_mut() {
+        match build_mode.placement {
+            Some((position, validity)) => {
+                visibility.is_visible = true;
+
+                sprite.custom_size = Some(size);
+
+                transform.translation = position.extend(transform.translation.z);
+
+                sprite.color = if validity.is_ok() {
+                    GHOST_VALID_COLOR
+                } else {
+                    GHOST_INVALID_COLOR
+                };
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+}
+
+pub fn place_structures(
+    players: Query<&ActionState<PlayerAction>, With<PlayerControlled>>,
+    mut build_mode: ResMut<BuildMode>,
+    mut structures: Query<&mut Transform, With<Structure>>,
+    mut place_towers: EventWriter<PlaceTower>,
+    mut place_walls: EventWriter<PlaceWall>,
+) {
+    let pressed = players
+        .get_single()
+        .map(|action_state| action_state.just_pressed(PlayerAction::PlaceStructure))
+        .unwrap_or(false);
+
+    if !build_mode.active || !pressed {
+        return;
+    }
+
+    let (position, validity) = match build_mode.placement {
+        Some(placement) => placement,
+        None => return,
+    };
+
+    if let Err(error) = validity {
+        info!("Cannot build at {}: {:?}", position, error);
+
+        return;
+    }
+
+    if let Some(structure) = build_mode.relocating.take() {
+        if let Ok(mut transform) = structures.get_mut(structure) {
+            transform.translation = position.extend(transform.translation.z);
+
+            info!("Relocated structure to {}", position);
+        }
+
+        return;
+    }
+
+    match build_mode.selected {
+        StructureKind::Tower(kind) => place_towers.send(PlaceTower { kind, position }),
+        StructureKind::Wall => place_walls.send(PlaceWall { position }),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn structure_under_cursor(
+    cursor: Vec2,
+    structures: &Query<(Entity, &Structure, &GlobalTransform, &Collider)>,
+) -> Option<(Entity, StructureKind, u32)> {
+    structures
+        .iter()
+        .find(|(_, _, transform, collider)| {
+            collide(
+                cursor.extend(0.0),
+                Vec2::ONE,
+                transform.translation(),
+                collider.size,
+            )
+            .is_some()
+        })
+        .map(|(entity, structure, _, _)| (entity, structure.kind, structure.cost))
+}
+
+pub fn sell_and_relocate_structures(
+    players: Query<&ActionState<PlayerAction>, With<PlayerControlled>>,
+    mouse_positions: Query<&MousePosition2d>,
+    structures: Query<(Entity, &Structure, &GlobalTransform, &Collider)>,
+    mut build_mode: ResMut<BuildMode>,
+    mut bank: Bank,
+    mut commands: Commands,
+) {
+    let action_state = match players.get_single() {
+        Ok(action_state) => action_state,
+        Err(_) => return,
+    };
+
+    let cursor = match mouse_positions.get_single() {
+        Ok(mouse_position) => mouse_position.world_pos,
+        Err(_) => return,
+    };
+
+    if !build_mode.active {
+        return;
+    }
+
+    let (structure, kind, cost) = match structure_under_cursor(cursor, &structures) {
+        Some(structure) => structure,
+        None => return,
+    };
+
+    if action_state.just_pressed(PlayerAction::SellStructure) {
+        let refund = (cost as f32 * SELL_REFUND) as u32;
+
+        bank.deposit(refund, TransactionReason::Sale(kind));
+
+        if build_mode.relocating == Some(structure) {
+            build_mode.relocating = None;
+        }
+
+        commands.entity(structure).despawn_recursive();
+
+        info!("Sold {:?} for {}", kind, refund);
+    } else if action_state.just_pressed(PlayerAction::RelocateStructure) {
+        build_mode.relocating = Some(structure);
+
+        info!("Relocating {:?}, place it to confirm", kind);
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+
+use super::{hud::UiFont, player::PlayerControlled, shared::DamageEvent, waves::WaveCompleted};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DifficultyPreset {
+    Easy,
+    Normal,
+    Hard,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DifficultyModifiers {
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32,
+    pub spawn_rate: f32,
+}
+
+impl DifficultyModifiers {
+    pub fn scale_health(&self, health: u16) -> u16 {
+        (health as f32 * self.health).round().clamp(1.0, u16::MAX as f32) as u16
+    }
+
+    pub fn scale_speed(&self, speed: i32) -> i32 {
+        (speed as f32 * self.speed).round() as i32
+    }
+
+    pub fn scale_damage(&self, damage: u16) -> u16 {
+        (damage as f32 * self.damage).round().clamp(0.0, u16::MAX as f32) as u16
+    }
+
+    pub fn scale_spawn_interval(&self, interval_sec: f32) -> f32 {
+        interval_sec / self.spawn_rate
+    }
+}
+
+impl DifficultyPreset {
+    pub fn modifiers(&self) -> DifficultyModifiers {
+        match self {
+            DifficultyPreset::Easy => DifficultyModifiers {
+                health: 0.75,
+                speed: 0.85,
+                damage: 0.6,
+                spawn_rate: 0.75,
+            },
+            DifficultyPreset::Normal => DifficultyModifiers {
+                health: 1.0,
+                speed: 1.0,
+                damage: 1.0,
+                spawn_rate: 1.0,
+            },
+            DifficultyPreset::Hard => DifficultyModifiers {
+                health: 1.4,
+                speed: 1.15,
+                damage: 1.5,
+                spawn_rate: 1.3,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PlayerPerformance {
+    pub damage_taken_in_wave: u32,
+    pub last_wave_clear_sec: Option<f32>,
+}
+
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    /// Whether `performance_factor` follows how well the player is doing
+    pub adaptive: bool,
+    pub performance: PlayerPerformance,
+    /// Multiplier on top of the preset, raised when waves are cleared quickly without taking damage
+    pub performance_factor: f32,
+}
+
+impl Difficulty {
+    const MIN_PERFORMANCE_FACTOR: f32 = 0.7;
+    const MAX_PERFORMANCE_FACTOR: f32 = 1.5;
+    const PERFORMANCE_STEP: f32 = 0.1;
+    const TARGET_CLEAR_SEC: f32 = 60.0;
+    const TARGET_DAMAGE_TAKEN: u32 = 100;
+
+    pub fn new(preset: DifficultyPreset, adaptive: bool) -> Self {
+        Difficulty {
+            preset,
+            adaptive,
+            performance: PlayerPerformance::default(),
+            performance_factor: 1.0,
+        }
+    }
+
+    pub fn modifiers(&self) -> DifficultyModifiers {
+        let preset = self.preset.modifiers();
+
+        if !self.adaptive {
+            return preset;
+        }
+
+        let factor = self.performance_factor;
+
+        DifficultyModifiers {
+            health: preset.health * factor,
+            speed: preset.speed * (1.0 + (factor - 1.0) * 0.5),
+            damage: preset.damage * factor,
+            spawn_rate: preset.spawn_rate * factor,
+        }
+    }
+
+    fn rate_wave(&mut self, clear_sec: f32) {
+        let damage_taken = self.performance.damage_taken_in_wave;
+
+        if clear_sec < Self::TARGET_CLEAR_SEC && damage_taken < Self::TARGET_DAMAGE_TAKEN {
+            self.performance_factor += Self::PERFORMANCE_STEP;
+        } else if clear_sec > 2.0 * Self::TARGET_CLEAR_SEC || damage_taken > 2 * Self::TARGET_DAMAGE_TAKEN {
+            self.performance_factor -= Self::PERFORMANCE_STEP;
+        }
+
+        self.performance_factor = self
+            .performance_factor
+            .clamp(Self::MIN_PERFORMANCE_FACTOR, Self::MAX_PERFORMANCE_FACTOR);
+
+        self.performance.last_wave_clear_sec = Some(clear_sec);
+        self.performance.damage_taken_in_wave = 0;
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::new(DifficultyPreset::Normal, true)
+    }
+}
+
+/// Current modifiers and player performance in the bottom left corner of the screen
+pub struct DifficultyDebugReadout {
+    pub enabled: bool,
+    pub refresh: Timer,
+}
+
+impl Default for DifficultyDebugReadout {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            refresh: Timer::from_seconds(0.5, true),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DifficultyReadoutText;
+
+pub fn track_player_performance(
+    mut damage_events: EventReader<DamageEvent>,
+    mut wave_completed: EventReader<WaveCompleted>,
+    players: Query<(), With<PlayerControlled>>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    for damage in damage_events.iter() {
+        if players.get(damage.target).is_ok() {
+            difficulty.performance.damage_taken_in_wave += damage.amount as u32;
+        }
+    }
+
+    for wave in wave_completed.iter() {
+        difficulty.rate_wave(wave.duration_sec);
+
+        if difficulty.adaptive {
+            info!(
+                "Wave {} rated, difficulty performance factor is now {:.2}",
+                wave.wave_number, difficulty.performance_factor
+            );
+        }
+    }
+}
+
+pub fn toggle_difficulty_debug_readout(
+    keys: Res<Input<KeyCode>>,
+    mut readout: ResMut<DifficultyDebugReadout>,
+) {
+    if keys.just_pressed(KeyCode::F4) {
+        readout.enabled = !readout.enabled;
+
+        info!("Difficulty debug readout {}", if readout.enabled { "enabled" } else { "disabled" });
+    }
+}
+
+fn readout_text(difficulty: &Difficulty) -> String {
+    let modifiers = difficulty.modifiers();
+
+    format!(
+        "Difficulty {:?} (adaptive: {}, factor {:.2})\n\
+         health x{:.2} speed x{:.2} damage x{:.2} spawn rate x{:.2}\n\
+         damage taken this wave {}, last clear {:?}s",
+        difficulty.preset,
+        difficulty.adaptive,
+        difficulty.performance_factor,
+        modifiers.health,
+        modifiers.speed,
+        modifiers.damage,
+        modifiers.spawn_rate,
+        difficulty.performance.damage_taken_in_wave,
+        difficulty.performance.last_wave_clear_sec,
+    )
+}
+
+pub fn show_difficulty_debug_readout(
+    mut texts: Query<(Entity, &mut Text), With<DifficultyReadoutText>>,
+    difficulty: Res<Difficulty>,
+    mut readout: ResMut<DifficultyDebugReadout>,
+    font: Res<UiFont>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    if !readout.enabled {
+        for (text, _) in texts.iter() {
+            commands.entity(text).despawn();
+        }
+
+        return;
+    }
+
+    if texts.is_empty() {
+        commands
+            .spawn_bundle(
+                TextBundle::from_section(readout_text(&difficulty), font.style(16.0, Color::YELLOW))
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(12.0),
+                            bottom: Val::Px(12.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+            )
+            .insert(DifficultyReadoutText);
+
+        return;
+    }
+
+    if !readout.refresh.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    for (_, mut text) in texts.iter_mut() {
+        text.sections[0].value = readout_text(&difficulty);
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{build::StructureKind, shared::DeathEvent, tower::TowerKind, waves::WaveCompleted};
+
+/// Currency granted when the entity is destroyed
+#[derive(Component, Clone, Copy)]
+pub struct Bounty(pub u32);
+
+pub struct Wallet {
+    pub balance: u32,
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self { balance: 200 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionReason {
+    Bounty,
+    WaveBonus { wave_number: u32 },
+    Tower(TowerKind),
+    Wall,
+    Sale(StructureKind),
+    Repair,
+    Pickup,
+    Upgrade(String),
+}
+
+/// Every change of the wallet balance, `amount` is negative for spending
+pub struct TransactionEvent {
+    pub amount: i64,
+    pub reason: TransactionReason,
+    pub balance: u32,
+}
+
+#[derive(Debug)]
+pub struct InsufficientFunds {
+    pub cost: u32,
+    pub balance: u32,
+}
+
+/// Only way to change the `Wallet`, so every change is validated and emitted as a `TransactionEvent`
+#[derive(SystemParam)]
+pub struct Bank<'w, 's> {
+    wallet: ResMut<'w, Wallet>,
+    transactions: EventWriter<'w, 's, TransactionEvent>,
+}
+
+impl<'w, 's> Bank<'w, 's> {
+    pub fn balance(&self) -> u32 {
+        self.wallet.balance
+    }
+
+    pub fn can_afford(&self, cost: u32) -> bool {
+        self.wallet.balance >= cost
+    }
+
+    pub fn deposit(&mut self, amount: u32, reason: TransactionReason) {
+        self.wallet.balance = self.wallet.balance.saturating_add(amount);
+
+        self.transactions.send(TransactionEvent {
+            amount: amount as i64,
+            reason,
+            balance: self.wallet.balance,
+        });
+    }
+
+    /// Deducts `cost` only when the whole amount can be paid, otherwise leaves the balance untouched
+    pub fn try_purchase(
+        &mut self,
+        cost: u32,
+        reason: TransactionReason,
+    ) -> Result<u32, InsufficientFunds> {
+        let balance = self.wallet.balance;
+
+        let new_balance = balance
+            .checked_sub(cost)
+            .ok_or(InsufficientFunds { cost, balance })?;
+
+        self.wallet.balance = new_balance;
+
+        self.transactions.send(TransactionEvent {
+            amount: -(cost as i64),
+            reason,
+            balance: new_balance,
+        });
+
+        return Ok(new_balance);
+    }
+}
+
+const WAVE_BONUS_BASE: u32 = 50;
+const WAVE_BONUS_PER_WAVE: u32 = 25;
+
+pub fn wave_completion_bonus(wave_number: u32) -> u32 {
+    WAVE_BONUS_BASE + WAVE_BONUS_PER_WAVE * wave_number
+}
+
+pub fn pay_bounties(
+    mut deaths: EventReader<DeathEvent>,
+    bounties: Query<&Bounty>,
+    mut bank: Bank,
+) {
+    for death in deaths.iter() {
+        if let Ok(bounty) = bounties.get(death.entity) {
+            bank.deposit(bounty.0, TransactionReason::Bounty);
+        }
+    }
+}
+
+pub fn pay_wave_bonuses(mut wave_completed: EventReader<WaveCompleted>, mut bank: Bank) {
+    for wave in wave_completed.iter() {
+        bank.deposit(
+            wave_completion_bonus(wave.wave_number),
+            TransactionReason::WaveBonus {
+                wave_number: wave.wave_number,
+            },
+        );
+    }
+}
+
+pub fn log_transactions(mut transactions: EventReader<TransactionEvent>) {
+    for transaction in transactions.iter() {
+        debug!(
+            "{:+} currency ({:?}), balance {}",
+            transaction.amount, transaction.reason, transaction.balance
+        );
+    }
+}
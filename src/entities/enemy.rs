@@ -1,21 +1,135 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
+use serde::Deserialize;
 
 use super::{
-    ai::enemy_ai::Idle,
-    shared::{DisplayName, EntitySharedBundle, Movable},
+    ai::{enemy_ai::Idle, perception::Perception},
+    shared::{CollisionMask, DisplayName, EntitySharedBundle, Movable},
+    sprites::SpriteDefinitions,
+    weapons::Weapon,
 };
 
 #[derive(Component, Default)]
 pub struct Enemy;
 
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum EnemyArchetype {
+    Grunt,
+    Scout,
+    Heavy,
+    /// Shares the player's tank body and turret, see `spawn_enemy_tank`
+    Tank,
+}
+
+impl Default for EnemyArchetype {
+    fn default() -> Self {
+        EnemyArchetype::Grunt
+    }
+}
+
+impl EnemyArchetype {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyArchetype::Grunt => "Grunt",
+            EnemyArchetype::Scout => "Scout",
+            EnemyArchetype::Heavy => "Heavy",
+            EnemyArchetype::Tank => "Tank",
+        }
+    }
+
+    pub fn max_health(&self) -> u16 {
+        match self {
+            EnemyArchetype::Grunt => 100,
+            EnemyArchetype::Scout => 50,
+            EnemyArchetype::Heavy => 250,
+            EnemyArchetype::Tank => 200,
+        }
+    }
+
+    pub fn speed(&self) -> i32 {
+        match self {
+            EnemyArchetype::Grunt => 50,
+            EnemyArchetype::Scout => 90,
+            EnemyArchetype::Heavy => 30,
+            EnemyArchetype::Tank => 70,
+        }
+    }
+
+    pub fn bounty(&self) -> u32 {
+        match self {
+            EnemyArchetype::Grunt => 10,
+            EnemyArchetype::Scout => 15,
+            EnemyArchetype::Heavy => 30,
+            EnemyArchetype::Tank => 40,
+        }
+    }
+
+    pub fn perception(&self) -> Perception {
+        match self {
+            EnemyArchetype::Grunt => Perception::default(),
+            EnemyArchetype::Scout => Perception::new(450.0, f32::to_radians(120.0)),
+            EnemyArchetype::Heavy => Perception::new(250.0, f32::to_radians(60.0)),
+            EnemyArchetype::Tank => Perception::new(400.0, f32::to_radians(90.0)),
+        }
+    }
+
+    /// Archetypes with a behavior tree are driven by it instead of `Idle`
+    pub fn behavior_tree(&self) -> Option<&'static str> {
+        match self {
+            EnemyArchetype::Scout => Some("ai/scout.bt.ron"),
+            _ => None,
+        }
+    }
+
+    /// Points for killing an enemy of this archetype, before the combo multiplier
+    pub fn score(&self) -> u32 {
+        match self {
+            EnemyArchetype::Grunt => 100,
+            EnemyArchetype::Scout => 150,
+            EnemyArchetype::Heavy => 300,
+            EnemyArchetype::Tank => 400,
+        }
+    }
+
+    /// Loot table rolled when an enemy of this archetype dies
+    pub fn loot_table(&self) -> &'static str {
+        match self {
+            EnemyArchetype::Grunt => "loot/grunt.loot.ron",
+            EnemyArchetype::Scout => "loot/scout.loot.ron",
+            EnemyArchetype::Heavy => "loot/heavy.loot.ron",
+            EnemyArchetype::Tank => "loot/tank.loot.ron",
+        }
+    }
+
+    pub fn weapon(&self) -> Option<Weapon> {
+        match self {
+            EnemyArchetype::Scout => Some(Weapon::new(1.0, 5, 150.0, vec![CollisionMask::PLAYER])),
+            _ => None,
+        }
+    }
+
+    /// Sprite definition of the archetype, the body for tanks
+    pub fn sprite_definition(&self) -> &'static str {
+        match self {
+            EnemyArchetype::Grunt => "sprites/grunt.sprite.ron",
+            EnemyArchetype::Scout => "sprites/scout.sprite.ron",
+            EnemyArchetype::Heavy => "sprites/heavy.sprite.ron",
+            EnemyArchetype::Tank => "sprites/enemy_tank.sprite.ron",
+        }
+    }
+}
+
 #[derive(Bundle, Default)]
 pub struct EnemyBundle {
     pub enemy: Enemy,
 
+    pub archetype: EnemyArchetype,
+
     pub ai: Idle,
 
+    pub perception: Perception,
+
     pub movable: Movable,
 
     #[bundle]
@@ -43,4 +157,17 @@ impl EnemyBundle {
             ..Default::default()
         }
     }
+
+    pub fn from_archetype(archetype: EnemyArchetype, sprites: &SpriteDefinitions) -> Self {
+        let mut enemy = EnemyBundle::new(archetype.name());
+
+        let definition = sprites.get(archetype.sprite_definition());
+
+        enemy.archetype = archetype;
+        enemy.movable.speed = archetype.speed();
+        enemy.shared = EntitySharedBundle::from_definition(archetype.name(), definition);
+        enemy.perception = archetype.perception();
+
+        enemy
+    }
 }
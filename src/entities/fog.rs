@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_mouse_position_component::MousePosition2d;
+
+use super::{
+    enemy::Enemy,
+    level::Tile,
+    navigation::NavGrid,
+    shared::{Collider, DisplayName},
+};
+
+pub const PLAYER_VISION_RADIUS: f32 = 450.0;
+
+/// Brightness of explored tiles that are out of vision
+const REMEMBERED_BRIGHTNESS: f32 = 0.35;
+
+/// Reveals the fog around the entity, carried by the player tank, towers and allied units
+#[derive(Component, Clone, Copy)]
+pub struct VisionSource {
+    pub radius: f32,
+}
+
+impl VisionSource {
+    pub fn new(radius: f32) -> Self {
+        VisionSource { radius }
+    }
+}
+
+/// What is known about every navigation cell, kept apart from rendering so it works headless
+#[derive(Default)]
+pub struct FogOfWar {
+    /// Cells inside the vision of any vision source right now
+    visible: HashSet<IVec2>,
+    /// Cells that were visible at some point
+    explored: HashSet<IVec2>,
+}
+
+impl FogOfWar {
+    pub fn is_cell_visible(&self, cell: IVec2) -> bool {
+        self.visible.contains(&cell)
+    }
+
+    pub fn is_cell_explored(&self, cell: IVec2) -> bool {
+        self.explored.contains(&cell)
+    }
+
+    pub fn is_visible(&self, position: Vec2, nav_grid: &NavGrid) -> bool {
+        self.is_cell_visible(nav_grid.cell(position))
+    }
+
+    pub fn is_explored(&self, position: Vec2, nav_grid: &NavGrid) -> bool {
+        self.is_cell_explored(nav_grid.cell(position))
+    }
+
+    /// Cells seen by the given sources, each one a position and its vision radius
+    pub fn visible_cells(
+        sources: impl IntoIterator<Item = (Vec2, f32)>,
+        nav_grid: &NavGrid,
+    ) -> HashSet<IVec2> {
+        sources
+            .into_iter()
+            .flat_map(|(position, radius)| cells_in_radius(position, radius, nav_grid))
+            .collect()
+    }
+
+    /// Makes exactly the cells seen by `sources` visible and remembers them as explored.
+    /// Returns whether the visible cells changed.
+    pub fn update(
+        &mut self,
+        sources: impl IntoIterator<Item = (Vec2, f32)>,
+        nav_grid: &NavGrid,
+    ) -> bool {
+        let visible = FogOfWar::visible_cells(sources, nav_grid);
+
+        return self.reveal(visible);
+    }
+
+    fn reveal(&mut self, visible: HashSet<IVec2>) -> bool {
+        if self.visible == visible {
+            return false;
+        }
+
+        self.explored.extend(visible.iter().copied());
+        self.visible = visible;
+
+        return true;
+    }
+}
+
+/// Enemy under the mouse cursor, enemies hidden in the fog cannot be targeted
+#[derive(Default)]
+pub struct CursorTarget(pub Option<Entity>);
+
+fn cells_in_radius(center: Vec2, radius: f32, nav_grid: &NavGrid) -> Vec<IVec2> {
+    let center_cell = nav_grid.cell(center);
+    let cell_radius = (radius / nav_grid.cell_size).ceil() as i32;
+
+    let mut cells = Vec::new();
+
+    for x in -cell_radius..=cell_radius {
+        for y in -cell_radius..=cell_radius {
+            let cell = center_cell + IVec2::new(x, y);
+
+            if nav_grid.cell_center(cell).distance(center) <= radius {
+                cells.push(cell);
+            }
+        }
+    }
+
+    return cells;
+}
+
+pub fn update_fog_of_war(
+    sources: Query<(&GlobalTransform, &VisionSource)>,
+    nav_grid: Res<NavGrid>,
+    mut fog: ResMut<FogOfWar>,
+) {
+    let sources = sources
+        .iter()
+        .map(|(transform, source)| (transform.translation().truncate(), source.radius));
+
+    let visible = FogOfWar::visible_cells(sources, &nav_grid);
+
+    // only touching the resource on change keeps the tile shading from running every frame
+    if fog.visible != visible {
+        fog.reveal(visible);
+    }
+}
+
+/// Shows visible tiles as they are, remembered ones dimmed and unexplored ones black
+pub fn shade_fogged_tiles(
+    mut tiles: Query<(&Tile, &Transform, &mut Sprite)>,
+    fog: Res<FogOfWar>,
+    nav_grid: Res<NavGrid>,
+) {
+    if !fog.is_changed() {
+        return;
+    }
+
+    for (tile, transform, mut sprite) in tiles.iter_mut() {
+        let cell = nav_grid.cell(transform.translation.truncate());
+
+        let color = tile.terrain.color();
+
+        sprite.color = if fog.is_cell_visible(cell) {
+            color
+        } else if fog.is_cell_explored(cell) {
+            Color::rgb(
+                color.r() * REMEMBERED_BRIGHTNESS,
+                color.g() * REMEMBERED_BRIGHTNESS,
+                color.b() * REMEMBERED_BRIGHTNESS,
+            )
+        } else {
+            Color::BLACK
+        };
+    }
+}
+
+type FoggedEntities<'w, 's, T> = Query<
+    'w,
+    's,
+    (&'static GlobalTransform, &'static mut Visibility, Option<&'static Children>),
+    With<T>,
+>;
+
+fn show_when<T: Component>(
+    entities: &mut FoggedEntities<T>,
+    children_visibility: &mut Query<&mut Visibility, Without<T>>,
+    shown: impl Fn(Vec2) -> bool,
+) {
+    for (transform, mut visibility, children) in entities.iter_mut() {
+        let visible = shown(transform.translation().truncate());
+
+        if visibility.is_visible == visible {
+            continue;
+        }
+
+        visibility.is_visible = visible;
+
+        for child in children.into_iter().flatten() {
+            if let Ok(mut child_visibility) = children_visibility.get_mut(*child) {
+                child_visibility.is_visible = visible;
+            }
+        }
+    }
+}
+
+/// For things that move or vanish, like enemies and pickups, they are only shown while in vision
+pub fn hide_in_fog<T: Component>(
+    mut entities: FoggedEntities<T>,
+    mut children_visibility: Query<&mut Visibility, Without<T>>,
+    fog: Res<FogOfWar>,
+    nav_grid: Res<NavGrid>,
+) {
+    show_when(&mut entities, &mut children_visibility, |position| {
+        fog.is_visible(position, &nav_grid)
+    });
+}
+
+/// For things that stay where they are, like props, rubble and spawn points, they are remembered
+/// once explored
+pub fn hide_unexplored<T: Component>(
+    mut entities: FoggedEntities<T>,
+    mut children_visibility: Query<&mut Visibility, Without<T>>,
+    fog: Res<FogOfWar>,
+    nav_grid: Res<NavGrid>,
+) {
+    show_when(&mut entities, &mut children_visibility, |position| {
+        fog.is_explored(position, &nav_grid)
+    });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn target_enemy_under_cursor(
+    mouse_positions: Query<&MousePosition2d>,
+    enemies: Query<(Entity, &GlobalTransform, &Collider, &DisplayName), With<Enemy>>,
+    fog: Res<FogOfWar>,
+    nav_grid: Res<NavGrid>,
+    mut cursor_target: ResMut<CursorTarget>,
+) {
+    let cursor = match mouse_positions.get_single() {
+        Ok(mouse_position) => mouse_position.world_pos,
+        Err(_) => return,
+    };
+
+    let target = enemies
+        .iter()
+        .filter(|(_, transform, _, _)| {
+            fog.is_visible(transform.translation().truncate(), &nav_grid)
+        })
+        .find(|(_, transform, collider, _)| {
+            let half_size = collider.size / 2.0;
+            let delta = (cursor - transform.translation().truncate()).abs();
+
+            delta.x <= half_size.x && delta.y <= half_size.y
+        });
+
+    let target_entity = target.map(|(entity, _, _, _)| entity);
+
+    if cursor_target.0 == target_entity {
+        return;
+    }
+
+    if let Some((_, _, _, name)) = target {
+        debug!("Targeting {}", name.0);
+    }
+
+    cursor_target.0 = target_entity;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_in_vision_are_visible_and_explored() {
+        let nav_grid = NavGrid::default();
+        let mut fog = FogOfWar::default();
+
+        assert!(fog.update([(Vec2::ZERO, 100.0)], &nav_grid));
+
+        assert!(fog.is_visible(Vec2::ZERO, &nav_grid));
+        assert!(fog.is_explored(Vec2::ZERO, &nav_grid));
+        assert!(fog.is_cell_visible(IVec2::new(1, 0)));
+        assert!(!fog.is_cell_visible(IVec2::new(2, 0)));
+    }
+
+    #[test]
+    fn cells_left_behind_stay_explored() {
+        let nav_grid = NavGrid::default();
+        let mut fog = FogOfWar::default();
+
+        fog.update([(Vec2::ZERO, 100.0)], &nav_grid);
+        fog.update([(Vec2::new(640.0, 0.0), 100.0)], &nav_grid);
+
+        assert!(!fog.is_cell_visible(IVec2::ZERO));
+        assert!(fog.is_cell_explored(IVec2::ZERO));
+        assert!(fog.is_cell_visible(IVec2::new(10, 0)));
+    }
+
+    #[test]
+    fn cells_never_seen_are_hidden() {
+        let nav_grid = NavGrid::default();
+        let mut fog = FogOfWar::default();
+
+        fog.update([(Vec2::ZERO, 100.0)], &nav_grid);
+
+        assert!(!fog.is_cell_visible(IVec2::new(5, 5)));
+        assert!(!fog.is_cell_explored(IVec2::new(5, 5)));
+    }
+
+    #[test]
+    fn unchanged_vision_is_not_an_update() {
+        let nav_grid = NavGrid::default();
+        let mut fog = FogOfWar::default();
+
+        assert!(fog.update([(Vec2::ZERO, 100.0)], &nav_grid));
+        assert!(!fog.update([(Vec2::new(10.0, 0.0), 100.0)], &nav_grid));
+    }
+}
@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use super::{
+    ai::perception::PerceptionTarget,
+    enemy::Enemy,
+    layers::ZLayers,
+    shared::{Collider, CollisionMask, DamageEvent, DeathEvent, EntitySharedBundle, Health},
+    sprites::{SpriteDefinitions, HEADQUARTERS_SPRITE},
+    GameState,
+};
+
+#[derive(Component)]
+pub struct Headquarters {
+    /// Enemies touching the headquarters damage it on every tick
+    pub contact_damage: u16,
+    pub contact_timer: Timer,
+}
+
+impl Default for Headquarters {
+    fn default() -> Self {
+        Self {
+            contact_damage: 10,
+            contact_timer: Timer::from_seconds(1.0, true),
+        }
+    }
+}
+
+pub struct HqDamaged {
+    pub amount: u16,
+    pub remaining_health: u16,
+}
+
+/// Current state of the headquarters for the HUD
+#[derive(Default)]
+pub struct HqStatus {
+    pub health: u16,
+    pub max_health: u16,
+    pub destroyed: bool,
+}
+
+const HQ_HEALTH: u16 = 1000;
+
+pub fn spawn_headquarters(
+    position: Vec2,
+    status: &mut HqStatus,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) {
+    let definition = sprites.get(HEADQUARTERS_SPRITE);
+
+    let mut shared = EntitySharedBundle::from_definition("Headquarters", definition);
+
+    shared.sprite.transform.translation = position.extend(layers.units);
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Headquarters::default())
+        .insert(Health::new(HQ_HEALTH))
+        .insert(definition.collider(vec![CollisionMask::PLAYER]))
+        .insert(PerceptionTarget::default());
+
+    *status = HqStatus {
+        health: HQ_HEALTH,
+        max_health: HQ_HEALTH,
+        destroyed: false,
+    };
+
+    info!("Spawned headquarters");
+}
+
+#[allow(clippy::type_complexity)]
+pub fn damage_headquarters_on_contact(
+    mut headquarters: Query<(Entity, &mut Headquarters, &mut Health, &GlobalTransform, &Collider)>,
+    enemies: Query<(&GlobalTransform, &Collider), With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (hq_entity, mut hq, mut health, hq_transform, hq_collider) in headquarters.iter_mut() {
+        if !hq.contact_timer.tick(time.delta()).just_finished() || health.is_dead() {
+            continue;
+        }
+
+        let hq_position = hq_transform.translation().truncate();
+
+        let hq_size = hq_collider.size.x;
+
+        let attackers = enemies
+            .iter()
+            .filter(|(enemy_transform, enemy_collider)| {
+                let reach = (hq_size + enemy_collider.size.x) / 2.0;
+
+                enemy_transform.translation().truncate().distance(hq_position) <= reach + 8.0
+            })
+            .count() as u16;
+
+        if attackers == 0 {
+            continue;
+        }
+
+        let damage = hq.contact_damage.saturating_mul(attackers);
+
+        damage_events.send(DamageEvent {
+            target: hq_entity,
+            amount: damage,
+        });
+
+        if health.try_apply_damage(damage).is_none() {
+            deaths.send(DeathEvent {
+                entity: hq_entity,
+                position: hq_position,
+                killed_by: None,
+            });
+
+            commands.entity(hq_entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn track_headquarters_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    headquarters: Query<&Health, With<Headquarters>>,
+    mut status: ResMut<HqStatus>,
+    mut hq_damaged: EventWriter<HqDamaged>,
+) {
+    for damage in damage_events.iter() {
+        let health = match headquarters.get(damage.target) {
+            Ok(health) => health,
+            Err(_) => continue,
+        };
+
+        status.health = health.current_health;
+
+        hq_damaged.send(HqDamaged {
+            amount: damage.amount,
+            remaining_health: health.current_health,
+        });
+    }
+}
+
+pub fn end_game_when_headquarters_destroyed(
+    headquarters: Query<(), With<Headquarters>>,
+    mut status: ResMut<HqStatus>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if status.destroyed || status.max_health == 0 || !headquarters.is_empty() {
+        return;
+    }
+
+    status.destroyed = true;
+    status.health = 0;
+
+    info!("Headquarters destroyed, game over");
+
+    if let Err(error) = game_state.set(GameState::GameOver) {
+        warn!("Could not enter game over state: {:?}", error);
+    }
+}
+
+pub fn show_game_over_screen(mut commands: Commands) {
+    commands.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            ..Default::default()
+        },
+        color: UiColor(Color::rgba(0.3, 0.0, 0.0, 0.7)),
+        ..Default::default()
+    });
+}
@@ -0,0 +1,21 @@
+use bevy::prelude::*;
+
+const UI_FONT: &str = "fonts/DejaVuSans.ttf";
+
+/// Font of every text drawn on screen or in the world
+#[derive(Default)]
+pub struct UiFont(pub Handle<Font>);
+
+impl UiFont {
+    pub fn style(&self, font_size: f32, color: Color) -> TextStyle {
+        TextStyle {
+            font: self.0.clone(),
+            font_size,
+            color,
+        }
+    }
+}
+
+pub fn load_ui_font(mut font: ResMut<UiFont>, asset_server: Res<AssetServer>) {
+    font.0 = asset_server.load(UI_FONT);
+}
@@ -0,0 +1,35 @@
+/// Z of every kind of sprite in the world, all spawners place their sprites through it so the
+/// draw order is decided in one place
+pub struct ZLayers {
+    /// Level tiles
+    pub ground: f32,
+    /// Wrecks, rubble and pickups lying on the ground
+    pub decals: f32,
+    /// Tanks, enemies, structures and props
+    pub units: f32,
+    /// Turrets on top of the unit carrying them
+    pub turrets: f32,
+    pub projectiles: f32,
+    /// Health bars, banners and the build ghost placed in world space
+    pub ui: f32,
+}
+
+impl Default for ZLayers {
+    fn default() -> Self {
+        Self {
+            ground: -1.0,
+            decals: -0.5,
+            units: 0.0,
+            turrets: 1.0,
+            projectiles: 2.0,
+            ui: 5.0,
+        }
+    }
+}
+
+impl ZLayers {
+    /// Local z of a turret, which is a child of the unit carrying it
+    pub fn turret_offset(&self) -> f32 {
+        self.turrets - self.units
+    }
+}
@@ -0,0 +1,601 @@
+use std::fmt;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    ai::perception::BlocksSight,
+    arena::{generate_arena, ArenaParams},
+    bounds::WorldBounds,
+    hq::{spawn_headquarters, HqStatus},
+    layers::ZLayers,
+    lives::PlayerSpawnPoint,
+    navigation::{NavBlocker, NavGrid},
+    prop::{spawn_prop, PropKind},
+    shared::{Collider, CollisionMask, Solid},
+    spawn_points::{spawn_spawn_point, SpawnPoint, SpawnPointActivation, SpawnTableEntry},
+    spawner::spawn_player_tank,
+    sprites::SpriteDefinitions,
+    terrain::Terrain,
+    tower::{spawn_tower, TowerDefinitions, TowerKind},
+};
+
+const DEFAULT_LEVEL: &str = "maps/crossroads.map.ron";
+
+/// What a character in the `tiles` grid of a map file stands for
+enum TileSymbol {
+    Terrain(Terrain),
+    PlayerSpawn,
+    Headquarters,
+}
+
+impl TileSymbol {
+    fn parse(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' => Some(TileSymbol::Terrain(Terrain::Grass)),
+            '=' => Some(TileSymbol::Terrain(Terrain::Road)),
+            ',' => Some(TileSymbol::Terrain(Terrain::Mud)),
+            ':' => Some(TileSymbol::Terrain(Terrain::Sand)),
+            '~' => Some(TileSymbol::Terrain(Terrain::ShallowWater)),
+            '#' => Some(TileSymbol::Terrain(Terrain::Rock)),
+            'P' => Some(TileSymbol::PlayerSpawn),
+            'H' => Some(TileSymbol::Headquarters),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnPointDef {
+    pub name: String,
+    pub tile: (i32, i32),
+    pub spawn_table: Vec<SpawnTableEntry>,
+    pub cooldown_sec: f32,
+    pub max_alive: usize,
+    pub activation: SpawnPointActivation,
+    /// Tiles enemies from this spawn point walk through on their way to the headquarters
+    #[serde(default)]
+    pub path: Vec<(i32, i32)>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TowerPlacementDef {
+    pub kind: TowerKind,
+    pub tile: (i32, i32),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PropPlacementDef {
+    pub kind: PropKind,
+    pub tile: (i32, i32),
+}
+
+/// Level layout as written in `.map.ron` asset files
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelMapDef {
+    pub name: String,
+    /// Rows from top to bottom with one character per tile, see `TileSymbol::parse`
+    pub tiles: Vec<String>,
+    #[serde(default)]
+    pub spawn_points: Vec<SpawnPointDef>,
+    #[serde(default)]
+    pub towers: Vec<TowerPlacementDef>,
+    #[serde(default)]
+    pub props: Vec<PropPlacementDef>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Empty,
+    UnevenRow { row: usize },
+    UnknownTile { symbol: char, tile: IVec2 },
+    MissingPlayerSpawn,
+    MissingHeadquarters,
+    /// A second player spawn or headquarters, a map has exactly one of each
+    DuplicateTile { symbol: char, tile: IVec2 },
+    /// A spawn point, path or tower is placed outside the map or on a blocking tile
+    NotPlaceable { what: String, tile: IVec2 },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Empty => write!(f, "map has no tiles"),
+            LevelError::UnevenRow { row } => {
+                write!(f, "row {} differs in length from the first row", row)
+            }
+            LevelError::UnknownTile { symbol, tile } => {
+                write!(f, "unknown tile '{}' at {}", symbol, tile)
+            }
+            LevelError::MissingPlayerSpawn => write!(f, "map has no player spawn 'P'"),
+            LevelError::MissingHeadquarters => write!(f, "map has no headquarters 'H'"),
+            LevelError::DuplicateTile { symbol, tile } => {
+                write!(f, "map has a second '{}' at {}", symbol, tile)
+            }
+            LevelError::NotPlaceable { what, tile } => {
+                write!(f, "{} cannot be placed at {}", what, tile)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// Validated level, tiles are addressed by (column, row) from the top left corner
+#[derive(TypeUuid)]
+#[uuid = "9b3d5e21-7f4c-4a8e-b6d2-1c0a8f3e5d47"]
+pub struct LevelMap {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    terrain: Vec<Terrain>,
+    pub player_spawn: IVec2,
+    pub headquarters: IVec2,
+    pub spawn_points: Vec<SpawnPointDef>,
+    pub towers: Vec<TowerPlacementDef>,
+    pub props: Vec<PropPlacementDef>,
+}
+
+impl LevelMap {
+    pub fn from_definition(definition: LevelMapDef) -> Result<Self, LevelError> {
+        let height = definition.tiles.len() as i32;
+        let width = definition.tiles.first().map_or(0, |row| row.chars().count()) as i32;
+
+        if width == 0 || height == 0 {
+            return Err(LevelError::Empty);
+        }
+
+        let mut terrain = Vec::new();
+        let mut player_spawn = None;
+        let mut headquarters = None;
+
+        for (row, line) in definition.tiles.iter().enumerate() {
+            if line.chars().count() as i32 != width {
+                return Err(LevelError::UnevenRow { row });
+            }
+
+            for (column, symbol) in line.chars().enumerate() {
+                let tile = IVec2::new(column as i32, row as i32);
+
+                let tile_terrain = match TileSymbol::parse(symbol) {
+                    Some(TileSymbol::Terrain(tile_terrain)) => tile_terrain,
+                    Some(TileSymbol::PlayerSpawn) => {
+                        if player_spawn.replace(tile).is_some() {
+                            return Err(LevelError::DuplicateTile { symbol, tile });
+                        }
+
+                        Terrain::Grass
+                    }
+                    Some(TileSymbol::Headquarters) => {
+                        if headquarters.replace(tile).is_some() {
+                            return Err(LevelError::DuplicateTile { symbol, tile });
+                        }
+
+                        Terrain::Grass
+                    }
+                    None => return Err(LevelError::UnknownTile { symbol, tile }),
+                };
+
+                terrain.push(tile_terrain);
+            }
+        }
+
+        let map = LevelMap {
+            name: definition.name,
+            width,
+            height,
+            terrain,
+            player_spawn: player_spawn.ok_or(LevelError::MissingPlayerSpawn)?,
+            headquarters: headquarters.ok_or(LevelError::MissingHeadquarters)?,
+            spawn_points: definition.spawn_points,
+            towers: definition.towers,
+            props: definition.props,
+        };
+
+        for spawn_point in map.spawn_points.iter() {
+            let tiles = std::iter::once(&spawn_point.tile).chain(spawn_point.path.iter());
+
+            for tile in tiles {
+                map.check_placeable(&format!("spawn point {}", spawn_point.name), *tile)?;
+            }
+        }
+
+        for tower in map.towers.iter() {
+            map.check_placeable(&format!("{:?} tower", tower.kind), tower.tile)?;
+        }
+
+        for prop in map.props.iter() {
+            map.check_placeable(&format!("{:?} prop", prop.kind), prop.tile)?;
+        }
+
+        return Ok(map);
+    }
+
+    fn check_placeable(&self, what: &str, tile: (i32, i32)) -> Result<(), LevelError> {
+        let tile = IVec2::from(tile);
+
+        match self.terrain(tile) {
+            Some(terrain) if !terrain.is_blocking() => Ok(()),
+            _ => Err(LevelError::NotPlaceable {
+                what: what.to_string(),
+                tile,
+            }),
+        }
+    }
+
+    pub fn terrain(&self, tile: IVec2) -> Option<Terrain> {
+        if tile.x < 0 || tile.y < 0 || tile.x >= self.width || tile.y >= self.height {
+            return None;
+        }
+
+        self.terrain.get((tile.y * self.width + tile.x) as usize).copied()
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (IVec2, Terrain)> + '_ {
+        let width = self.width;
+
+        self.terrain.iter().enumerate().map(move |(index, terrain)| {
+            let index = index as i32;
+
+            (IVec2::new(index % width, index / width), *terrain)
+        })
+    }
+
+    /// Navigation cell of a tile, the map is centered on the origin and rows grow downwards
+    pub fn cell(&self, tile: IVec2) -> IVec2 {
+        IVec2::new(tile.x - self.width / 2, self.height / 2 - tile.y)
+    }
+
+    pub fn tile_position(&self, tile: IVec2, nav_grid: &NavGrid) -> Vec2 {
+        nav_grid.cell_center(self.cell(tile))
+    }
+}
+
+#[derive(Default)]
+pub struct LevelMapLoader;
+
+impl AssetLoader for LevelMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<LevelMapDef>(bytes)?;
+
+            let map = LevelMap::from_definition(definition)?;
+
+            load_context.set_default_asset(LoadedAsset::new(map));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+/// Level that is played, it is spawned once its map finished loading
+pub struct CurrentLevel {
+    pub path: String,
+    /// Generates an arena from the seed and parameters instead of loading the map at `path`
+    pub arena: Option<(u64, ArenaParams)>,
+    pub map: Option<Handle<LevelMap>>,
+    pub spawned: bool,
+}
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_LEVEL.to_string(),
+            arena: None,
+            map: None,
+            spawned: false,
+        }
+    }
+}
+
+impl CurrentLevel {
+    pub fn arena(seed: u64, params: ArenaParams) -> Self {
+        Self {
+            arena: Some((seed, params)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Ground tile of the level
+#[derive(Component)]
+pub struct Tile {
+    pub terrain: Terrain,
+}
+
+fn spawn_tile(
+    terrain: Terrain,
+    position: Vec2,
+    size: f32,
+    layers: &ZLayers,
+    commands: &mut Commands,
+) {
+    let mut tile = commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: terrain.color(),
+            custom_size: Some(Vec2::splat(size)),
+            ..Default::default()
+        },
+        transform: Transform::from_translation(position.extend(layers.ground)),
+        ..Default::default()
+    });
+
+    tile.insert(Tile { terrain });
+
+    if terrain.is_blocking() {
+        tile.insert(Solid)
+            .insert(Collider::new(
+                vec![CollisionMask::PLAYER, CollisionMask::ENEMY],
+                Vec2::splat(size),
+            ))
+            .insert(BlocksSight)
+            .insert(NavBlocker);
+    }
+}
+
+/// Spawns the tiles and everything placed on the map, and fits the navigation grid and the world
+/// bounds to it
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_level(
+    map: &LevelMap,
+    nav_grid: &mut NavGrid,
+    bounds: &mut WorldBounds,
+    player_spawn: &mut PlayerSpawnPoint,
+    hq_status: &mut HqStatus,
+    tower_definitions: &TowerDefinitions,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) {
+    nav_grid.min_cell = map.cell(IVec2::new(0, map.height - 1));
+    nav_grid.size = IVec2::new(map.width, map.height);
+
+    let half_cell = Vec2::splat(nav_grid.cell_size / 2.0);
+
+    *bounds = WorldBounds::new(
+        map.tile_position(IVec2::new(0, map.height - 1), nav_grid) - half_cell,
+        map.tile_position(IVec2::new(map.width - 1, 0), nav_grid) + half_cell,
+    );
+
+    nav_grid.clear_terrain();
+
+    for (tile, terrain) in map.tiles() {
+        nav_grid.set_terrain(map.cell(tile), terrain);
+
+        let position = map.tile_position(tile, nav_grid);
+
+        spawn_tile(terrain, position, nav_grid.cell_size, layers, commands);
+    }
+
+    let headquarters_position = map.tile_position(map.headquarters, nav_grid);
+
+    spawn_headquarters(headquarters_position, hq_status, layers, sprites, commands);
+
+    for definition in map.spawn_points.iter() {
+        let path = definition
+            .path
+            .iter()
+            .map(|tile| map.tile_position(IVec2::from(*tile), nav_grid))
+            .collect();
+
+        let spawn_point = SpawnPoint::new(
+            &definition.name,
+            definition.spawn_table.clone(),
+            definition.cooldown_sec,
+            definition.max_alive,
+            definition.activation,
+        )
+        .with_path(path);
+
+        let position = map.tile_position(IVec2::from(definition.tile), nav_grid);
+
+        spawn_spawn_point(spawn_point, position, layers, sprites, commands);
+    }
+
+    for placement in map.towers.iter() {
+        if let Some(definition) = tower_definitions.get(placement.kind) {
+            let position = map.tile_position(IVec2::from(placement.tile), nav_grid);
+
+            spawn_tower(definition, placement.kind, position, layers, sprites, commands);
+        }
+    }
+
+    for placement in map.props.iter() {
+        let position = map.tile_position(IVec2::from(placement.tile), nav_grid);
+
+        spawn_prop(placement.kind, position, layers, sprites, commands);
+    }
+
+    player_spawn.0 = map.tile_position(map.player_spawn, nav_grid);
+
+    spawn_player_tank(player_spawn.0, layers, sprites, commands);
+
+    info!("Spawned level {}", map.name);
+}
+
+/// Starts loading the map file, a generated arena is added to the map assets right away
+pub fn load_current_level(
+    mut level: ResMut<CurrentLevel>,
+    mut maps: ResMut<Assets<LevelMap>>,
+    asset_server: Res<AssetServer>,
+) {
+    let (seed, params) = match level.arena {
+        Some(arena) => arena,
+        None => {
+            level.map = Some(asset_server.load(level.path.as_str()));
+
+            return;
+        }
+    };
+
+    match LevelMap::from_definition(generate_arena(seed, &params)) {
+        Ok(map) => {
+            info!("Generated arena {}x{} from seed {}", map.width, map.height, seed);
+
+            level.map = Some(maps.add(map));
+        }
+        Err(error) => {
+            error!("Generated arena from seed {} is invalid: {}", seed, error);
+
+            level.spawned = true;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_current_level_when_loaded(
+    mut level: ResMut<CurrentLevel>,
+    maps: Res<Assets<LevelMap>>,
+    asset_server: Res<AssetServer>,
+    mut nav_grid: ResMut<NavGrid>,
+    mut bounds: ResMut<WorldBounds>,
+    mut player_spawn: ResMut<PlayerSpawnPoint>,
+    mut hq_status: ResMut<HqStatus>,
+    tower_definitions: Res<TowerDefinitions>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    if level.spawned {
+        return;
+    }
+
+    // the player tank and the towers are spawned with the level, so their sprites and the tower
+    // definitions have to be ready
+    if !sprites.is_settled(&asset_server) || !tower_definitions.is_settled(&asset_server) {
+        return;
+    }
+
+    let handle = match level.map.clone() {
+        Some(handle) => handle,
+        None => return,
+    };
+
+    let map = match maps.get(&handle) {
+        Some(map) => map,
+        None => {
+            if asset_server.get_load_state(&handle) == LoadState::Failed {
+                error!("Could not load level {}", level.path);
+
+                level.spawned = true;
+            }
+
+            return;
+        }
+    };
+
+    spawn_level(
+        map,
+        &mut nav_grid,
+        &mut bounds,
+        &mut player_spawn,
+        &mut hq_status,
+        &tower_definitions,
+        &layers,
+        &sprites,
+        &mut commands,
+    );
+
+    level.spawned = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(tiles: &[&str]) -> LevelMapDef {
+        LevelMapDef {
+            name: "test".to_string(),
+            tiles: tiles.iter().map(|row| row.to_string()).collect(),
+            spawn_points: Vec::new(),
+            towers: Vec::new(),
+            props: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn valid_map_is_accepted() {
+        let map = LevelMap::from_definition(definition(&["P.=", ",#H"])).unwrap();
+
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(map.player_spawn, IVec2::new(0, 0));
+        assert_eq!(map.headquarters, IVec2::new(2, 1));
+        assert_eq!(map.terrain(IVec2::new(2, 0)), Some(Terrain::Road));
+        assert_eq!(map.terrain(IVec2::new(1, 1)), Some(Terrain::Rock));
+        assert_eq!(map.terrain(IVec2::new(3, 0)), None);
+    }
+
+    #[test]
+    fn empty_map_is_rejected() {
+        let result = LevelMap::from_definition(definition(&[]));
+
+        assert!(matches!(result, Err(LevelError::Empty)));
+    }
+
+    #[test]
+    fn uneven_rows_are_rejected() {
+        let result = LevelMap::from_definition(definition(&["P.", ".H."]));
+
+        assert!(matches!(result, Err(LevelError::UnevenRow { row: 1 })));
+    }
+
+    #[test]
+    fn unknown_tiles_are_rejected() {
+        let result = LevelMap::from_definition(definition(&["P.", "xH"]));
+
+        assert!(matches!(result, Err(LevelError::UnknownTile { symbol: 'x', .. })));
+    }
+
+    #[test]
+    fn maps_need_a_player_spawn_and_headquarters() {
+        let without_spawn = LevelMap::from_definition(definition(&["..", ".H"]));
+        let without_headquarters = LevelMap::from_definition(definition(&["P.", ".."]));
+
+        assert!(matches!(without_spawn, Err(LevelError::MissingPlayerSpawn)));
+        assert!(matches!(without_headquarters, Err(LevelError::MissingHeadquarters)));
+    }
+
+    #[test]
+    fn maps_with_a_second_player_spawn_or_headquarters_are_rejected() {
+        let two_spawns = LevelMap::from_definition(definition(&["P.", "PH"]));
+        let two_headquarters = LevelMap::from_definition(definition(&["PH", ".H"]));
+
+        assert!(matches!(
+            two_spawns,
+            Err(LevelError::DuplicateTile { symbol: 'P', tile }) if tile == IVec2::new(0, 1)
+        ));
+        assert!(matches!(
+            two_headquarters,
+            Err(LevelError::DuplicateTile { symbol: 'H', tile }) if tile == IVec2::new(1, 1)
+        ));
+    }
+
+    #[test]
+    fn towers_cannot_be_placed_on_rock_or_outside_the_map() {
+        for tile in [(1, 0), (5, 5)] {
+            let mut tower_definition = definition(&["P#", ".H"]);
+
+            tower_definition.towers.push(TowerPlacementDef {
+                kind: TowerKind::Gun,
+                tile,
+            });
+
+            let result = LevelMap::from_definition(tower_definition);
+
+            assert!(matches!(result, Err(LevelError::NotPlaceable { .. })));
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use super::{
+    layers::ZLayers,
+    loot::{PowerUpKind, PowerUps},
+    player::PlayerControlled,
+    shared::{DeathEvent, EntitySharedBundle, Health, Lifetime},
+    spawner::spawn_player_tank,
+    sprites::{SpriteDefinitions, WRECK_SPRITE},
+    GameState,
+};
+
+const PLAYER_LIVES: u32 = 3;
+const RESPAWN_DELAY_SEC: f32 = 3.0;
+const RESPAWN_INVULNERABILITY_SEC: f32 = 2.0;
+const WRECK_LIFETIME_SEC: f32 = 30.0;
+
+pub struct PlayerLives {
+    /// Lives left to respawn with, losing the tank without any ends the game
+    pub remaining: u32,
+    /// Running while the player is dead and a life is left to respawn with
+    pub respawn_timer: Option<Timer>,
+}
+
+impl Default for PlayerLives {
+    fn default() -> Self {
+        Self {
+            remaining: PLAYER_LIVES,
+            respawn_timer: None,
+        }
+    }
+}
+
+/// Base position the player tank spawns and respawns at
+#[derive(Default)]
+pub struct PlayerSpawnPoint(pub Vec2);
+
+/// Remains of a destroyed player tank
+#[derive(Component, Default)]
+pub struct Wreck;
+
+/// Player tank ignoring hits for a short time after respawning
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(duration_sec: f32) -> Self {
+        Invulnerable {
+            timer: Timer::from_seconds(duration_sec, false),
+        }
+    }
+}
+
+fn spawn_wreck(
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) {
+    let mut shared = EntitySharedBundle::from_definition("Wreck", sprites.get(WRECK_SPRITE));
+
+    shared.sprite.transform.translation = position.extend(layers.decals);
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Wreck)
+        .insert(Lifetime::new(WRECK_LIFETIME_SEC));
+}
+
+/// Leaves a wreck behind and consumes a life, the game ends when no lives remain. A tank hit by
+/// several lethal shots in one frame still only costs a single life.
+pub fn handle_player_death(
+    mut deaths: EventReader<DeathEvent>,
+    players: Query<(), With<PlayerControlled>>,
+    mut lives: ResMut<PlayerLives>,
+    mut game_state: ResMut<State<GameState>>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    let mut handled = HashSet::new();
+
+    for death in deaths.iter() {
+        if players.get(death.entity).is_err() || !handled.insert(death.entity) {
+            continue;
+        }
+
+        spawn_wreck(death.position, &layers, &sprites, &mut commands);
+
+        if lives.remaining == 0 {
+            info!("Player destroyed with no lives left, game over");
+
+            if let Err(error) = game_state.set(GameState::GameOver) {
+                warn!("Could not enter game over state: {:?}", error);
+            }
+
+            continue;
+        }
+
+        lives.remaining -= 1;
+        lives.respawn_timer = Some(Timer::from_seconds(RESPAWN_DELAY_SEC, false));
+
+        info!("Player destroyed, respawning with {} lives left", lives.remaining);
+    }
+}
+
+pub fn respawn_player(
+    mut lives: ResMut<PlayerLives>,
+    spawn_point: Res<PlayerSpawnPoint>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let respawn_due = match lives.respawn_timer.as_mut() {
+        Some(timer) => timer.tick(time.delta()).finished(),
+        None => return,
+    };
+
+    if !respawn_due {
+        return;
+    }
+
+    lives.respawn_timer = None;
+
+    let player = spawn_player_tank(spawn_point.0, &layers, &sprites, &mut commands);
+
+    commands
+        .entity(player)
+        .insert(Invulnerable::new(RESPAWN_INVULNERABILITY_SEC));
+
+    info!("Player respawned");
+}
+
+/// Keeps `Health.invulnerable` in sync with respawn invulnerability and the shield power-up
+#[allow(clippy::type_complexity)]
+pub fn update_invulnerability(
+    mut players: Query<
+        (
+            Entity,
+            &mut Health,
+            &mut Sprite,
+            Option<&mut Invulnerable>,
+            Option<&PowerUps>,
+        ),
+        With<PlayerControlled>,
+    >,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (player, mut health, mut sprite, invulnerable, power_ups) in players.iter_mut() {
+        let mut respawning = false;
+
+        if let Some(mut invulnerable) = invulnerable {
+            respawning = !invulnerable.timer.tick(time.delta()).finished();
+
+            if !respawning {
+                commands.entity(player).remove::<Invulnerable>();
+            }
+        }
+
+        let shielded = power_ups
+            .map_or(false, |power_ups| power_ups.is_active(PowerUpKind::Shield));
+
+        health.invulnerable = respawning || shielded;
+
+        sprite.color.set_a(if respawning { 0.5 } else { 1.0 });
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::collide_aabb::collide,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    bounds::WorldBounds,
+    economy::{Bank, TransactionReason},
+    enemy::EnemyArchetype,
+    layers::ZLayers,
+    player::{PlayerControlled, TankTurret},
+    shared::{Collider, DeathEvent, EntitySharedBundle, Health, Lifetime},
+    sprites::{SpriteDefinitions, PICKUP_SPRITE},
+    weapons::{Ammo, Weapon},
+};
+
+const PICKUP_LIFETIME_SEC: f32 = 12.0;
+
+/// Pickups of one drop are spread around the death position so they do not stack
+const PICKUP_SCATTER: f32 = 24.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum PowerUpKind {
+    DoubleDamage,
+    RapidFire,
+    Shield,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum LootKind {
+    Currency(u32),
+    HealthPack(u16),
+    Ammo(u32),
+    PowerUp { kind: PowerUpKind, duration_sec: f32 },
+}
+
+impl LootKind {
+    fn name(&self) -> String {
+        match self {
+            LootKind::Currency(_) => "Currency".to_string(),
+            LootKind::HealthPack(_) => "Health Pack".to_string(),
+            LootKind::Ammo(_) => "Ammo".to_string(),
+            LootKind::PowerUp { kind, .. } => format!("{:?}", kind),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            LootKind::Currency(_) => Color::rgb(1.0, 0.85, 0.0),
+            LootKind::HealthPack(_) => Color::rgb(0.1, 0.9, 0.2),
+            LootKind::Ammo(_) => Color::rgb(0.6, 0.5, 0.3),
+            LootKind::PowerUp { kind: PowerUpKind::DoubleDamage, .. } => Color::rgb(1.0, 0.2, 0.2),
+            LootKind::PowerUp { kind: PowerUpKind::RapidFire, .. } => Color::rgb(1.0, 0.5, 0.0),
+            LootKind::PowerUp { kind: PowerUpKind::Shield, .. } => Color::rgb(0.3, 0.6, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LootEntry {
+    pub weight: u32,
+    pub drop: LootKind,
+}
+
+/// Drops of an enemy as written in `.loot.ron` asset files, every roll drops one weighted entry
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "6c1f4a0e-5d38-4b7e-9a52-3e7d2b9c8f14"]
+pub struct LootTable {
+    #[serde(default = "LootTable::default_rolls")]
+    pub rolls: u32,
+    /// Chance of every roll to drop anything at all
+    pub drop_chance: f32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    fn default_rolls() -> u32 {
+        1
+    }
+
+    pub fn roll(&self) -> Vec<LootKind> {
+        let total_weight: u32 = self.entries.iter().map(|entry| entry.weight).sum();
+
+        if total_weight == 0 {
+            return Vec::new();
+        }
+
+        let mut drops = Vec::new();
+
+        for _ in 0..self.rolls {
+            if fastrand::f32() >= self.drop_chance {
+                continue;
+            }
+
+            let mut pick = fastrand::u32(0..total_weight);
+
+            for entry in self.entries.iter() {
+                if pick < entry.weight {
+                    drops.push(entry.drop);
+
+                    break;
+                }
+
+                pick -= entry.weight;
+            }
+        }
+
+        return drops;
+    }
+}
+
+#[derive(Default)]
+pub struct LootTableLoader;
+
+impl AssetLoader for LootTableLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let table = ron::de::from_bytes::<LootTable>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(table));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["loot.ron"]
+    }
+}
+
+/// Loot tables loaded up front, so the first kill of every kind can already drop something
+#[derive(Default)]
+pub struct LootTables {
+    pub handles: HashMap<String, Handle<LootTable>>,
+}
+
+pub const BOSS_LOOT_TABLE: &str = "loot/boss.loot.ron";
+
+pub fn load_loot_tables(mut loot_tables: ResMut<LootTables>, asset_server: Res<AssetServer>) {
+    let archetypes = [
+        EnemyArchetype::Grunt,
+        EnemyArchetype::Scout,
+        EnemyArchetype::Heavy,
+        EnemyArchetype::Tank,
+    ];
+
+    let paths = archetypes
+        .iter()
+        .map(|archetype| archetype.loot_table())
+        .chain([BOSS_LOOT_TABLE]);
+
+    for path in paths {
+        loot_tables
+            .handles
+            .insert(path.to_string(), asset_server.load(path));
+    }
+}
+
+/// Entity rolling the loot table at `path` when it dies
+#[derive(Component)]
+pub struct LootDropper {
+    pub path: String,
+}
+
+impl LootDropper {
+    pub fn new(path: &str) -> Self {
+        LootDropper {
+            path: path.to_string(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub loot: LootKind,
+}
+
+/// Power-ups currently running on the player tank
+#[derive(Component, Default)]
+pub struct PowerUps {
+    pub active: HashMap<PowerUpKind, Timer>,
+}
+
+impl PowerUps {
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.active.contains_key(&kind)
+    }
+
+    /// Starts the power-up, picking up one that is already running restarts its timer
+    pub fn activate(&mut self, kind: PowerUpKind, duration_sec: f32) {
+        self.active.insert(kind, Timer::from_seconds(duration_sec, false));
+    }
+}
+
+pub fn spawn_pickup(
+    loot: LootKind,
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let mut shared = EntitySharedBundle::from_definition(&loot.name(), sprites.get(PICKUP_SPRITE));
+
+    // every pickup shares one shape, the loot decides its color
+    shared.sprite.sprite.color = loot.color();
+    shared.sprite.transform.translation = position.extend(layers.decals);
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Pickup { loot })
+        .insert(Lifetime::new(PICKUP_LIFETIME_SEC))
+        .id()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn drop_loot_on_death(
+    mut deaths: EventReader<DeathEvent>,
+    droppers: Query<&LootDropper>,
+    loot_tables: Res<LootTables>,
+    tables: Res<Assets<LootTable>>,
+    bounds: Res<WorldBounds>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    let pickup_size = sprites.get(PICKUP_SPRITE).size();
+
+    for death in deaths.iter() {
+        let dropper = match droppers.get(death.entity) {
+            Ok(dropper) => dropper,
+            Err(_) => continue,
+        };
+
+        let table = match loot_tables
+            .handles
+            .get(&dropper.path)
+            .and_then(|handle| tables.get(handle))
+        {
+            Some(table) => table,
+            None => continue,
+        };
+
+        for loot in table.roll() {
+            let scatter = Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5) * 2.0;
+
+            let position = bounds.clamp(
+                death.position + scatter * PICKUP_SCATTER,
+                pickup_size,
+            );
+
+            spawn_pickup(loot, position, &layers, &sprites, &mut commands);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn collect_pickups(
+    mut players: Query<
+        (&GlobalTransform, &Collider, Option<&mut Health>, &mut PowerUps, &Children),
+        With<PlayerControlled>,
+    >,
+    pickups: Query<(Entity, &Pickup, &GlobalTransform)>,
+    mut turrets: Query<&mut Ammo, With<TankTurret>>,
+    mut bank: Bank,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    let pickup_size = sprites.get(PICKUP_SPRITE).size();
+
+    for (player_transform, player_collider, mut health, mut power_ups, children) in
+        players.iter_mut()
+    {
+        for (pickup_entity, pickup, pickup_transform) in pickups.iter() {
+            let touching = collide(
+                player_transform.translation(),
+                player_collider.size,
+                pickup_transform.translation(),
+                pickup_size,
+            )
+            .is_some();
+
+            if !touching {
+                continue;
+            }
+
+            match pickup.loot {
+                LootKind::Currency(amount) => bank.deposit(amount, TransactionReason::Pickup),
+                LootKind::HealthPack(amount) => {
+                    if let Some(health) = health.as_mut() {
+                        health.heal(amount);
+                    }
+                }
+                LootKind::Ammo(amount) => {
+                    for child in children.iter() {
+                        if let Ok(mut ammo) = turrets.get_mut(*child) {
+                            ammo.refill(amount);
+                        }
+                    }
+                }
+                LootKind::PowerUp { kind, duration_sec } => power_ups.activate(kind, duration_sec),
+            }
+
+            info!("Picked up {}", pickup.loot.name());
+
+            commands.entity(pickup_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Runs down power-up timers and applies rapid fire, the shield is applied in `update_invulnerability`
+pub fn tick_power_ups(
+    mut players: Query<(&mut PowerUps, &Children)>,
+    mut turrets: Query<&mut Weapon, With<TankTurret>>,
+    time: Res<Time>,
+) {
+    for (mut power_ups, children) in players.iter_mut() {
+        power_ups.active.retain(|kind, timer| {
+            let running = !timer.tick(time.delta()).finished();
+
+            if !running {
+                info!("{:?} wore off", kind);
+            }
+
+            running
+        });
+
+        // reloading twice as fast, the regular cooldown tick happens in `tick_weapon_cooldowns`
+        if power_ups.is_active(PowerUpKind::RapidFire) {
+            for child in children.iter() {
+                if let Ok(mut weapon) = turrets.get_mut(*child) {
+                    weapon.cooldown.tick(time.delta());
+                }
+            }
+        }
+    }
+}
@@ -1,25 +1,91 @@
 use bevy::prelude::*;
 
 use self::{
-    ai::enemy_ai::idle_enemy_behaviour,
-    projectiles::{despawn_entity_after_duration_expires, move_linear_particles, damage_entities_on_collision, rotate_homing_entities_towards_nearest_enemies},
+    ai::{
+        behavior_actions::register_default_behavior_actions,
+        behavior_tree::{run_behavior_trees, show_behavior_tree_debug_view, toggle_behavior_tree_debug_view, update_blackboards, BehaviorActions, BehaviorTree, BehaviorTreeDebugView, BehaviorTreeLoader},
+        enemy_ai::{chase_perceived_targets, idle_enemy_behaviour, transition_enemy_ai_on_perception},
+        perception::{perceive_gunfire, perceive_targets_by_sight, update_perception_facing, GunfireEvent},
+        squad::{break_formation_when_attacked, follow_squad_leaders, promote_squad_leaders, regroup_engaged_followers},
+        tank_ai::{drive_enemy_tanks, rotate_turrets_to_aim},
+    },
+    bounds::{despawn_projectiles_out_of_bounds, keep_movers_in_bounds, WorldBounds},
+    build::{handle_build_mode_input, place_structures, sell_and_relocate_structures, spawn_build_ghost, update_build_ghost, validate_build_placement, BuildMode},
+    boss::{advance_boss_phases, announce_boss_spawns, fire_boss_weapon_patterns, move_bosses, spawn_boss_minions, update_boss_health_bars, BossSpawned},
+    enemy::Enemy,
+    hq::{damage_headquarters_on_contact, end_game_when_headquarters_destroyed, show_game_over_screen, track_headquarters_damage, HqDamaged, HqStatus},
+    hud::{load_ui_font, UiFont},
+    layers::ZLayers,
+    level::{load_current_level, spawn_current_level_when_loaded, CurrentLevel, LevelMap, LevelMapLoader},
+    lives::{handle_player_death, respawn_player, update_invulnerability, PlayerLives, PlayerSpawnPoint},
+    loot::{collect_pickups, drop_loot_on_death, load_loot_tables, tick_power_ups, LootTable, LootTableLoader, LootTables, Pickup},
+    fog::{hide_in_fog, hide_unexplored, shade_fogged_tiles, target_enemy_under_cursor, update_fog_of_war, CursorTarget, FogOfWar},
+    economy::{log_transactions, pay_bounties, pay_wave_bonuses, TransactionEvent, Wallet},
+    difficulty::{show_difficulty_debug_readout, toggle_difficulty_debug_readout, track_player_performance, Difficulty, DifficultyDebugReadout},
+    navigation::{update_nav_grid, NavGrid},
+    projectiles::{despawn_entity_after_duration_expires, expire_slows, explode_projectiles_on_hit, move_linear_particles, damage_entities_on_collision, rotate_homing_entities_towards_nearest_enemies, slow_targets_on_hit, stop_projectiles_at_indestructible_solids, ProjectileHit},
+    prop::{clear_props_in_explosions, leave_rubble_of_destroyed_props, show_damaged_props, Prop, Rubble},
     player_input::{handle_player_firing, handle_player_movement, rotate_tank_tower_to_cursor},
+    repair::{repair_structures, self_repair_out_of_combat},
+    score::{award_kill_scores, award_long_range_hits, expire_combos, reset_combo_on_damage, show_run_summary, spawn_score_hud, update_score_hud, ScoreEvent, Scoreboard},
+    spawn_points::{log_destroyed_spawn_points, tick_spawn_point_cooldowns, SpawnPoint},
     spawner::*,
+    shared::{DamageEvent, DeathEvent},
+    sprites::{load_sprite_definitions, update_sprite_definitions, SpriteDefinition, SpriteDefinitionLoader, SpriteDefinitions},
+    terrain::apply_terrain_effects,
+    tower::{acquire_tower_targets, aim_and_fire_towers, load_tower_definitions, place_towers, update_tower_definitions, PlaceTower, TowerDefinition, TowerDefinitionLoader, TowerDefinitions},
+    upgrades::{apply_tank_upgrades, purchase_upgrades, request_upgrades_from_keys, PurchaseUpgrade, UpgradeTree},
+    walls::{attack_blocking_solids, place_walls, push_movers_out_of_solids, PlaceWall},
+    waves::{run_wave_director, WaveCompleted, WaveDirector},
+    weapons::tick_weapon_cooldowns,
 };
 
 pub mod ai;
+pub mod arena;
+pub mod boss;
+pub mod bounds;
+pub mod build;
+pub mod difficulty;
+pub mod economy;
 pub mod enemy;
+pub mod fog;
+pub mod hq;
+pub mod hud;
+pub mod layers;
+pub mod level;
+pub mod lives;
+pub mod loot;
+pub mod navigation;
 pub mod projectiles;
+pub mod prop;
 pub mod player;
 pub mod player_input;
+pub mod repair;
+pub mod score;
 pub mod shared;
+pub mod spawn_points;
 pub mod spawner;
+pub mod sprites;
+pub mod terrain;
+pub mod tower;
+pub mod upgrades;
+pub mod walls;
+pub mod waves;
+pub mod weapons;
 
 pub struct EntitiesPlugin;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Playing,
+    GameOver,
+}
+
 #[derive(SystemLabel)]
 enum GameSystems {
+    AiInput,
     PlayerInput,
+    Perception,
 }
 
 #[derive(SystemLabel)]
@@ -31,39 +97,223 @@ enum EntitySystems {
 
 impl Plugin for EntitiesPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<BossSpawned>();
+        app.add_event::<GunfireEvent>();
+        app.add_event::<DamageEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_event::<TransactionEvent>();
+        app.add_event::<WaveCompleted>();
+        app.add_event::<HqDamaged>();
+        app.add_event::<ProjectileHit>();
+        app.add_event::<PlaceTower>();
+        app.add_event::<PurchaseUpgrade>();
+        app.add_event::<PlaceWall>();
+        app.add_event::<ScoreEvent>();
+
+        app.add_state(GameState::Playing);
+
+        app.init_resource::<WaveDirector>();
+        app.init_resource::<BehaviorActions>();
+        app.init_resource::<BehaviorTreeDebugView>();
+        app.init_resource::<Difficulty>();
+        app.init_resource::<DifficultyDebugReadout>();
+        app.init_resource::<HqStatus>();
+        app.init_resource::<TowerDefinitions>();
+        app.init_resource::<Wallet>();
+        app.init_resource::<UpgradeTree>();
+        app.init_resource::<BuildMode>();
+        app.init_resource::<NavGrid>();
+        app.init_resource::<LootTables>();
+        app.init_resource::<PlayerLives>();
+        app.init_resource::<PlayerSpawnPoint>();
+        app.init_resource::<Scoreboard>();
+        app.init_resource::<CurrentLevel>();
+        app.init_resource::<WorldBounds>();
+        app.init_resource::<ZLayers>();
+        app.init_resource::<FogOfWar>();
+        app.init_resource::<CursorTarget>();
+        app.init_resource::<SpriteDefinitions>();
+        app.init_resource::<UiFont>();
+
+        app.add_asset::<BehaviorTree>()
+            .init_asset_loader::<BehaviorTreeLoader>();
+
+        app.add_asset::<LootTable>()
+            .init_asset_loader::<LootTableLoader>();
+
+        app.add_asset::<LevelMap>()
+            .init_asset_loader::<LevelMapLoader>();
+
+        app.add_asset::<SpriteDefinition>()
+            .init_asset_loader::<SpriteDefinitionLoader>();
+
+        app.add_asset::<TowerDefinition>()
+            .init_asset_loader::<TowerDefinitionLoader>();
+
+        // AI writes action states before they are consumed, so just pressed actions are not lost to the input tick
         app.add_system_set(
-            SystemSet::new()
+            SystemSet::on_update(GameState::Playing)
+                .before(GameSystems::PlayerInput)
+                .label(GameSystems::AiInput)
+                .with_system(drive_enemy_tanks),
+        );
+
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
                 .label(GameSystems::PlayerInput)
                 .with_system(handle_player_movement)
+                .with_system(tick_weapon_cooldowns)
                 .with_system(handle_player_firing)
                 .with_system(rotate_tank_tower_to_cursor)
+                .with_system(rotate_turrets_to_aim)
                 .with_system(damage_entities_on_collision)
+                .with_system(stop_projectiles_at_indestructible_solids)
                 .with_system(move_linear_particles)
+                .with_system(despawn_projectiles_out_of_bounds)
                 .with_system(rotate_homing_entities_towards_nearest_enemies)
                 .with_system(despawn_entity_after_duration_expires),
         );
 
         app.add_system_set(
-            SystemSet::new()
+            SystemSet::on_update(GameState::Playing)
                 .after(GameSystems::PlayerInput)
+                .label(GameSystems::Perception)
+                .with_system(update_perception_facing)
+                .with_system(perceive_targets_by_sight)
+                // gunfire is only remembered when no target is in sight, so sight goes first
+                .with_system(perceive_gunfire.after(perceive_targets_by_sight))
+                .with_system(
+                    update_blackboards
+                        .after(perceive_targets_by_sight)
+                        .after(perceive_gunfire),
+                )
+                .with_system(update_fog_of_war),
+        );
+
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .after(GameSystems::Perception)
                 .label(EntitySystems::Prespawn)
                 .with_system(log_enemies_on_spawn)
-                .with_system(idle_enemy_behaviour),
+                .with_system(transition_enemy_ai_on_perception)
+                .with_system(idle_enemy_behaviour)
+                .with_system(chase_perceived_targets)
+                .with_system(run_behavior_trees)
+                .with_system(follow_squad_leaders)
+                .with_system(promote_squad_leaders)
+                .with_system(break_formation_when_attacked)
+                .with_system(regroup_engaged_followers)
+                .with_system(toggle_behavior_tree_debug_view)
+                .with_system(show_behavior_tree_debug_view)
+                .with_system(track_player_performance)
+                .with_system(toggle_difficulty_debug_readout)
+                .with_system(show_difficulty_debug_readout)
+                .with_system(advance_boss_phases)
+                .with_system(move_bosses)
+                .with_system(fire_boss_weapon_patterns)
+                .with_system(spawn_boss_minions)
+                .with_system(announce_boss_spawns)
+                .with_system(update_boss_health_bars)
+                .with_system(tick_spawn_point_cooldowns)
+                .with_system(damage_headquarters_on_contact)
+                .with_system(track_headquarters_damage)
+                .with_system(end_game_when_headquarters_destroyed)
+                .with_system(explode_projectiles_on_hit)
+                .with_system(
+                    clear_props_in_explosions
+                        .after(explode_projectiles_on_hit)
+                        .after(attack_blocking_solids),
+                )
+                .with_system(show_damaged_props)
+                .with_system(slow_targets_on_hit)
+                .with_system(expire_slows)
+                // runs after the systems that change base speeds so it scales the new values
+                .with_system(
+                    apply_terrain_effects
+                        .after(apply_tank_upgrades)
+                        .after(advance_boss_phases),
+                )
+                .with_system(shade_fogged_tiles)
+                .with_system(hide_in_fog::<Enemy>)
+                .with_system(hide_in_fog::<Pickup>)
+                .with_system(hide_unexplored::<Prop>)
+                .with_system(hide_unexplored::<Rubble>)
+                .with_system(hide_unexplored::<SpawnPoint>)
+                .with_system(target_enemy_under_cursor)
+                .with_system(place_towers)
+                .with_system(acquire_tower_targets)
+                .with_system(aim_and_fire_towers)
+                .with_system(request_upgrades_from_keys)
+                .with_system(purchase_upgrades)
+                .with_system(apply_tank_upgrades)
+                .with_system(handle_build_mode_input)
+                .with_system(validate_build_placement)
+                .with_system(update_build_ghost)
+                .with_system(place_structures)
+                .with_system(sell_and_relocate_structures)
+                .with_system(place_walls)
+                .with_system(attack_blocking_solids)
+                .with_system(repair_structures)
+                .with_system(self_repair_out_of_combat)
+                .with_system(collect_pickups)
+                .with_system(tick_power_ups)
+                .with_system(respawn_player)
+                .with_system(update_invulnerability)
+                .with_system(expire_combos)
+                .with_system(spawn_current_level_when_loaded),
         );
 
         app.add_system_set(
-            SystemSet::new()
+            SystemSet::on_update(GameState::Playing)
                 .after(EntitySystems::Prespawn)
                 .label(EntitySystems::Spawn)
-                .with_system(spawn_enemies_on_demand),
+                .with_system(run_wave_director),
         );
 
-        app.add_startup_system(spawn_player);
+        app.add_startup_system(load_current_level);
+        app.add_startup_system(spawn_build_ghost);
+        app.add_startup_system(register_default_behavior_actions);
+        app.add_startup_system(load_loot_tables);
+        app.add_startup_system(load_sprite_definitions);
+        app.add_startup_system(load_tower_definitions);
+        app.add_startup_system(load_ui_font);
+        app.add_startup_system(spawn_score_hud.after(load_ui_font));
+
+        // copies loaded definitions before any system spawns with them
+        app.add_system_to_stage(CoreStage::PreUpdate, update_sprite_definitions);
+        app.add_system_to_stage(CoreStage::PreUpdate, update_tower_definitions);
+
+        app.add_system_to_stage(CoreStage::PostUpdate, log_destroyed_spawn_points);
+        app.add_system_to_stage(CoreStage::PostUpdate, update_nav_grid);
+
+        app.add_system_set(
+            SystemSet::on_enter(GameState::GameOver)
+                .with_system(show_game_over_screen)
+                .with_system(show_run_summary),
+        );
 
+        // reads death events of the whole frame while the destroyed entities are still around
         app.add_system_set(
-            SystemSet::new()
+            SystemSet::on_update(GameState::Playing)
                 .after(EntitySystems::Spawn)
-                .label(EntitySystems::PostSpawn),
+                .label(EntitySystems::PostSpawn)
+                .with_system(push_movers_out_of_solids)
+                .with_system(keep_movers_in_bounds)
+                .with_system(pay_bounties)
+                .with_system(drop_loot_on_death)
+                .with_system(leave_rubble_of_destroyed_props)
+                .with_system(handle_player_death)
+                .with_system(award_kill_scores)
+                .with_system(award_long_range_hits)
+                // a hit taken in the same frame breaks the combo before the kill is counted
+                .with_system(reset_combo_on_damage.before(award_kill_scores))
+                .with_system(
+                    update_score_hud
+                        .after(award_kill_scores)
+                        .after(award_long_range_hits),
+                )
+                .with_system(pay_wave_bonuses)
+                .with_system(log_transactions),
         );
     }
 }
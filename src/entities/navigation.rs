@@ -0,0 +1,371 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use bevy::prelude::*;
+
+use super::{shared::Collider, terrain::Terrain};
+
+/// Marks entities that occupy the navigation cells under their sprite
+#[derive(Component, Default)]
+pub struct NavBlocker;
+
+/// Grid the world is split into for pathfinding and structure placement
+pub struct NavGrid {
+    pub cell_size: f32,
+    /// Lowest corner cell, cells range from `min_cell` to `min_cell + size - 1`
+    pub min_cell: IVec2,
+    pub size: IVec2,
+    blocked: HashSet<IVec2>,
+    /// Ground of every cell, cells without an entry are grass
+    terrain: HashMap<IVec2, Terrain>,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        Self {
+            cell_size: 64.0,
+            min_cell: IVec2::new(-15, -15),
+            size: IVec2::new(31, 31),
+            blocked: HashSet::new(),
+            terrain: HashMap::new(),
+        }
+    }
+}
+
+const STRAIGHT_COST: u32 = 10;
+
+impl NavGrid {
+    pub fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).round().as_ivec2()
+    }
+
+    pub fn cell_center(&self, cell: IVec2) -> Vec2 {
+        cell.as_vec2() * self.cell_size
+    }
+
+    /// Center of the cell the position falls into
+    pub fn snap(&self, position: Vec2) -> Vec2 {
+        self.cell_center(self.cell(position))
+    }
+
+    pub fn in_bounds(&self, cell: IVec2) -> bool {
+        let max_cell = self.min_cell + self.size;
+
+        cell.x >= self.min_cell.x
+            && cell.y >= self.min_cell.y
+            && cell.x < max_cell.x
+            && cell.y < max_cell.y
+    }
+
+    pub fn is_blocked(&self, cell: IVec2) -> bool {
+        self.blocked.contains(&cell)
+    }
+
+    /// Replaces every blocked cell with `cells`
+    pub fn set_blocked(&mut self, cells: impl IntoIterator<Item = IVec2>) {
+        self.blocked = cells.into_iter().collect();
+    }
+
+    pub fn terrain(&self, cell: IVec2) -> Terrain {
+        self.terrain.get(&cell).copied().unwrap_or(Terrain::Grass)
+    }
+
+    pub fn set_terrain(&mut self, cell: IVec2, terrain: Terrain) {
+        self.terrain.insert(cell, terrain);
+    }
+
+    pub fn clear_terrain(&mut self) {
+        self.terrain.clear();
+    }
+
+    /// Cost of stepping into the cell, slow terrain costs more so paths prefer roads and avoid mud
+    fn step_cost(&self, cell: IVec2) -> u32 {
+        let speed_multiplier = self.terrain(cell).speed_multiplier().max(0.1);
+
+        (STRAIGHT_COST as f32 / speed_multiplier).round() as u32
+    }
+
+    /// Cells covered by an axis aligned box around `center`
+    pub fn cells_covered(&self, center: Vec2, size: Vec2) -> Vec<IVec2> {
+        // shrink slightly so a box exactly one cell wide does not spill into its neighbours
+        let half_size = (size / 2.0 - Vec2::splat(1.0)).max(Vec2::ZERO);
+
+        let min = self.cell(center - half_size);
+        let max = self.cell(center + half_size);
+
+        let mut cells = Vec::new();
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                cells.push(IVec2::new(x, y));
+            }
+        }
+
+        return cells;
+    }
+
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        self.find_path_where(from, to, |cell| !self.is_blocked(cell))
+    }
+
+    /// A* over the grid weighted by terrain, where `passable` decides which cells can be entered, the start cell is always allowed.
+    /// Idle enemies route along it through `NavRoute`, build validation uses it to keep a route open to the headquarters.
+    pub fn find_path_where(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        passable: impl Fn(IVec2) -> bool,
+    ) -> Option<Vec<Vec2>> {
+        let start = self.cell(from);
+        let goal = self.cell(to);
+
+        // the fastest terrain is assumed everywhere so the heuristic never overestimates
+        let min_step_cost = (STRAIGHT_COST as f32 / Terrain::MAX_SPEED_MULTIPLIER).floor() as u32;
+
+        let heuristic = |cell: IVec2| {
+            let delta = (goal - cell).abs();
+
+            (delta.x + delta.y) as u32 * min_step_cost
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+        let mut cost_so_far: HashMap<IVec2, u32> = HashMap::new();
+
+        open.push(Reverse((heuristic(start), start.x, start.y)));
+        cost_so_far.insert(start, 0);
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let current = IVec2::new(x, y);
+
+            if current == goal {
+                let mut path = vec![self.cell_center(current)];
+                let mut cell = current;
+
+                while let Some(previous) = came_from.get(&cell) {
+                    cell = *previous;
+                    path.push(self.cell_center(cell));
+                }
+
+                path.reverse();
+
+                return Some(path);
+            }
+
+            let current_cost = cost_so_far[&current];
+
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = current + offset;
+
+                if !self.in_bounds(next) || (next != goal && !passable(next)) {
+                    continue;
+                }
+
+                let next_cost = current_cost + self.step_cost(next);
+
+                if cost_so_far.get(&next).map_or(true, |cost| next_cost < *cost) {
+                    cost_so_far.insert(next, next_cost);
+                    came_from.insert(next, current);
+
+                    open.push(Reverse((next_cost + heuristic(next), next.x, next.y)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Cells an enemy drives along towards its objective, planned again when the objective or the grid changes
+#[derive(Default)]
+pub struct NavRoute {
+    objective: Option<Vec2>,
+    cells: Vec<Vec2>,
+    /// Cell the enemy last passed closest to, the route never steers back behind it
+    next: usize,
+}
+
+impl NavRoute {
+    /// Point to head for from `position`, the objective itself when no route around the blockers exists
+    pub fn steer(
+        &mut self,
+        nav_grid: &NavGrid,
+        position: Vec2,
+        objective: Vec2,
+        grid_changed: bool,
+    ) -> Vec2 {
+        if grid_changed || self.objective != Some(objective) {
+            self.objective = Some(objective);
+            self.cells = nav_grid.find_path(position, objective).unwrap_or_default();
+            self.next = 0;
+        }
+
+        // idle wandering overshoots and drifts, so pick up the route at the nearest cell left on it
+        let nearest = (self.next..self.cells.len()).min_by(|a, b| {
+            let distance_a = self.cells[*a].distance(position);
+            let distance_b = self.cells[*b].distance(position);
+
+            distance_a.total_cmp(&distance_b)
+        });
+
+        if let Some(nearest) = nearest {
+            self.next = nearest;
+        }
+
+        return self.cells.get(self.next + 1).copied().unwrap_or(objective);
+    }
+}
+
+/// Rebuilds the blocked cells whenever a blocker is added, moved or removed
+#[allow(clippy::type_complexity)]
+pub fn update_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    blockers: Query<(&Transform, &Collider), With<NavBlocker>>,
+    changed_blockers: Query<(), (With<NavBlocker>, Or<(Added<NavBlocker>, Changed<Transform>)>)>,
+    removed_blockers: RemovedComponents<NavBlocker>,
+) {
+    if changed_blockers.is_empty() && removed_blockers.iter().next().is_none() {
+        return;
+    }
+
+    let blocked: Vec<IVec2> = blockers
+        .iter()
+        .flat_map(|(transform, collider)| {
+            nav_grid.cells_covered(transform.translation.truncate(), collider.size)
+        })
+        .collect();
+
+    nav_grid.set_blocked(blocked);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(nav_grid: &NavGrid, x: i32, y: i32) -> Vec2 {
+        nav_grid.cell_center(IVec2::new(x, y))
+    }
+
+    #[test]
+    fn snaps_positions_to_cell_centers() {
+        let nav_grid = NavGrid::default();
+
+        assert_eq!(nav_grid.cell(Vec2::new(70.0, -20.0)), IVec2::new(1, 0));
+        assert_eq!(nav_grid.snap(Vec2::new(70.0, -20.0)), Vec2::new(64.0, 0.0));
+        assert!(nav_grid.in_bounds(IVec2::new(15, -15)));
+        assert!(!nav_grid.in_bounds(IVec2::new(16, 0)));
+    }
+
+    #[test]
+    fn finds_straight_path_on_empty_grid() {
+        let nav_grid = NavGrid::default();
+
+        let path = nav_grid
+            .find_path(position(&nav_grid, 0, 0), position(&nav_grid, 3, 0))
+            .unwrap();
+
+        let expected: Vec<Vec2> = (0..=3).map(|x| position(&nav_grid, x, 0)).collect();
+
+        assert_eq!(path, expected);
+    }
+
+    #[test]
+    fn goes_around_blocked_cells() {
+        let mut nav_grid = NavGrid::default();
+
+        nav_grid.set_blocked((-2..=2).map(|y| IVec2::new(1, y)));
+
+        let path = nav_grid
+            .find_path(position(&nav_grid, 0, 0), position(&nav_grid, 2, 0))
+            .unwrap();
+
+        assert!(path
+            .iter()
+            .all(|point| !nav_grid.is_blocked(nav_grid.cell(*point))));
+        assert_eq!(path.last(), Some(&position(&nav_grid, 2, 0)));
+        // around the 5 cell wall: 3 up or down, 2 across and 3 back
+        assert_eq!(path.len(), 9);
+    }
+
+    #[test]
+    fn prefers_roads_over_mud() {
+        let mut nav_grid = NavGrid::default();
+
+        // mud on the direct row, a road one row up
+        for x in 1..=3 {
+            nav_grid.set_terrain(IVec2::new(x, 0), Terrain::Mud);
+        }
+
+        for x in 0..=4 {
+            nav_grid.set_terrain(IVec2::new(x, 1), Terrain::Road);
+        }
+
+        let path = nav_grid
+            .find_path(position(&nav_grid, 0, 0), position(&nav_grid, 4, 0))
+            .unwrap();
+
+        assert!(path
+            .iter()
+            .all(|point| nav_grid.terrain(nav_grid.cell(*point)) != Terrain::Mud));
+    }
+
+    #[test]
+    fn no_path_to_enclosed_goal() {
+        let mut nav_grid = NavGrid::default();
+
+        let goal = IVec2::new(5, 5);
+
+        nav_grid.set_blocked(
+            [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|offset| goal + offset),
+        );
+
+        let path = nav_grid.find_path(position(&nav_grid, 0, 0), nav_grid.cell_center(goal));
+
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn route_steers_around_blocked_cells_and_replans_on_change() {
+        let mut nav_grid = NavGrid::default();
+
+        nav_grid.set_blocked((-2..=2).map(|y| IVec2::new(1, y)));
+
+        let start = position(&nav_grid, 0, 0);
+        let objective = position(&nav_grid, 4, 0);
+
+        let mut route = NavRoute::default();
+
+        let waypoint = route.steer(&nav_grid, start, objective, false);
+
+        // the wall is in the way, so the route leaves the start row instead of heading straight on
+        assert_ne!(waypoint.y, start.y);
+        assert!(!nav_grid.is_blocked(nav_grid.cell(waypoint)));
+
+        nav_grid.set_blocked([]);
+
+        let waypoint = route.steer(&nav_grid, start, objective, true);
+
+        assert_eq!(waypoint, position(&nav_grid, 1, 0));
+    }
+
+    #[test]
+    fn route_falls_back_to_objective_without_path() {
+        let mut nav_grid = NavGrid::default();
+
+        let goal = IVec2::new(5, 5);
+
+        nav_grid.set_blocked(
+            [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|offset| goal + offset),
+        );
+
+        let objective = nav_grid.cell_center(goal);
+
+        let mut route = NavRoute::default();
+
+        let waypoint = route.steer(&nav_grid, position(&nav_grid, 0, 0), objective, false);
+
+        assert_eq!(waypoint, objective);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use super::shared::Movable;
+use super::{shared::Movable, sprites::SpriteDefinition};
 
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub enum PlayerAction {
@@ -10,13 +10,41 @@ pub enum PlayerAction {
     TurnLeft,
     TurnRight,
     FireCannon,
+    ToggleBuildMode,
+    CycleBuildSelection,
+    PlaceStructure,
+    SellStructure,
+    RelocateStructure,
+    Repair,
 }
 
+pub const TANK_SPEED: i32 = 100;
+pub const TANK_MAX_HEALTH: u16 = 200;
+pub const TANK_ROTATION_SPEED_DEG: f32 = 80.0;
+pub const TURRET_ROTATION_SPEED_DEG: f32 = 180.0;
+
 #[derive(Component, Default)]
 pub struct PlayerControlled;
 
-#[derive(Bundle, Default)]
+/// Identifies a player across respawns of their tank, scores are kept per player
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PlayerId(pub u32);
+
+/// The only player for now, also credited with the kills of the towers
+pub const LOCAL_PLAYER: PlayerId = PlayerId(0);
+
+/// Tank body moved by its `ActionState<PlayerAction>`, whether written by input or by AI
+#[derive(Component, Default)]
+pub struct Tank;
+
+/// Tank turret firing from its `ActionState<PlayerAction>`
+#[derive(Component, Default)]
+pub struct TankTurret;
+
+#[derive(Bundle)]
 pub struct TankBundle {
+    pub tank: Tank,
+
     pub movable: Movable,
 
     #[bundle]
@@ -29,34 +57,26 @@ pub struct TankBodyBundle {
     pub sprite: SpriteBundle,
 }
 
-impl Default for TankBodyBundle {
-    fn default() -> Self {
-        let tank_body_sprite = Sprite {
-            color: Color::rgb(1.0, 0.0, 0.0),
-            custom_size: Some(Vec2 { x: 64.0, y: 64.0 }), // FIXME asset sizes should be in a bevy asset
-            ..Default::default()
-        };
-
+impl TankBodyBundle {
+    pub fn from_definition(definition: &SpriteDefinition) -> Self {
         TankBodyBundle {
-            sprite: SpriteBundle {
-                sprite: tank_body_sprite,
-                ..Default::default()
-            },
+            sprite: definition.sprite_bundle(),
         }
     }
 }
 
 impl TankBundle {
-    pub fn new() -> Self {
+    pub fn new(body: &SpriteDefinition) -> Self {
         let tank_bundle = TankBundle {
+            tank: Tank,
             movable: Movable {
-                speed: 100,
-                rotation_speed_rad: f32::to_radians(80.0),
+                speed: TANK_SPEED,
+                rotation_speed_rad: f32::to_radians(TANK_ROTATION_SPEED_DEG),
                 ..Default::default()
             },
-            ..Default::default()
+            tank_body: TankBodyBundle::from_definition(body),
         };
 
         tank_bundle
     }
-}
\ No newline at end of file
+}
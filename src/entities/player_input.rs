@@ -4,8 +4,18 @@ use bevy_transform_utils::get_angle_from_transform;
 use leafwing_input_manager::prelude::*;
 
 use super::{
-    player::{ PlayerAction, PlayerControlled},
-    shared::{Movable, MouseControlled}, spawner::create_projectile,
+    ai::perception::GunfireEvent,
+    build::BuildMode,
+    fog::CursorTarget,
+    layers::ZLayers,
+    loot::{PowerUpKind, PowerUps},
+    player::{PlayerAction, PlayerId, Tank, TankTurret},
+    repair::RepairTool,
+    shared::{CollisionMask, Movable, MouseControlled},
+    projectiles::{FiredBy, Projectile},
+    spawner::HomeTowardsEnemies,
+    sprites::SpriteDefinitions,
+    weapons::{spawn_weapon_projectile, Ammo, Weapon},
 };
 
 pub fn get_input_manager() -> InputManagerBundle<PlayerAction> {
@@ -16,7 +26,13 @@ pub fn get_input_manager() -> InputManagerBundle<PlayerAction> {
         .insert(KeyCode::S, PlayerAction::MoveBackwards)
         .insert(KeyCode::A, PlayerAction::TurnLeft)
         .insert(KeyCode::D, PlayerAction::TurnRight)
-        .insert(MouseButton::Left, PlayerAction::FireCannon);
+        .insert(MouseButton::Left, PlayerAction::FireCannon)
+        .insert(KeyCode::B, PlayerAction::ToggleBuildMode)
+        .insert(KeyCode::Tab, PlayerAction::CycleBuildSelection)
+        .insert(MouseButton::Left, PlayerAction::PlaceStructure)
+        .insert(MouseButton::Right, PlayerAction::SellStructure)
+        .insert(KeyCode::R, PlayerAction::RelocateStructure)
+        .insert(KeyCode::E, PlayerAction::Repair);
 
     InputManagerBundle::<PlayerAction> {
         action_state: ActionState::default(),
@@ -24,10 +40,11 @@ pub fn get_input_manager() -> InputManagerBundle<PlayerAction> {
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn handle_player_movement(
     mut query: Query<
         (&ActionState<PlayerAction>, &mut Transform, &Movable),
-        With<PlayerControlled>,
+        With<Tank>,
     >,
     time: Res<Time>,
 ) {
@@ -53,41 +70,129 @@ pub fn handle_player_movement(
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn handle_player_firing(
-    mut query: Query<(&ActionState<PlayerAction>, &GlobalTransform), With<MouseControlled>>,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &GlobalTransform,
+            &mut Weapon,
+            Option<&mut Ammo>,
+            Option<&MouseControlled>,
+            Option<&Parent>,
+        ),
+        With<TankTurret>,
+    >,
+    repair_tools: Query<&RepairTool>,
+    power_ups: Query<&PowerUps>,
+    player_ids: Query<&PlayerId>,
+    mut gunfire: EventWriter<GunfireEvent>,
+    cursor_target: Res<CursorTarget>,
+    build_mode: Res<BuildMode>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
     mut commands: Commands,
 ) {
-    for (action_state, global_transform) in query.iter_mut() {
-        if action_state.just_pressed(PlayerAction::FireCannon) {
-            let projectile_sprite = Sprite {
-                custom_size: Some(Vec2::new(20.0, 20.0)),
-                color: Color::rgb(0.0, 0.0, 1.0),
-                ..Default::default()
-            };
+    for (shooter, action_state, global_transform, mut weapon, ammo, mouse_controlled, parent) in
+        query.iter_mut()
+    {
+        // the left mouse button places structures while building
+        if build_mode.active && mouse_controlled.is_some() {
+            continue;
+        }
 
-            let transform = global_transform.compute_transform();
+        let repairing = parent
+            .and_then(|parent| repair_tools.get(parent.get()).ok())
+            .map_or(false, |repair_tool| repair_tool.is_repairing());
 
-            let projectile_pos = transform.translation; // FIXME here we inherit towers z position, should be instead some constant in some struct
+        if repairing {
+            continue;
+        }
 
-            let projectile_rotation = transform.rotation;
+        let loaded = ammo.as_ref().map_or(true, |ammo| ammo.current > 0);
 
-            let duration_sec = 20.0;
+        // clicks while reloading are dropped
+        if action_state.just_pressed(PlayerAction::FireCannon) && loaded && weapon.try_fire() {
+            if let Some(mut ammo) = ammo {
+                ammo.current -= 1;
+            }
 
-            let particle_speed = 100.0;
+            let transform = global_transform.compute_transform();
 
-            create_projectile(&mut commands, projectile_sprite, projectile_pos, projectile_rotation, duration_sec, particle_speed);
+            let projectile_pos = transform.translation.truncate();
+
+            let projectile_rotation = transform.rotation;
+
+            let projectile = spawn_weapon_projectile(
+                &mut commands,
+                &layers,
+                &sprites,
+                &weapon,
+                projectile_pos,
+                projectile_rotation,
+            );
+
+            let double_damage = parent
+                .and_then(|parent| power_ups.get(parent.get()).ok())
+                .map_or(false, |power_ups| power_ups.is_active(PowerUpKind::DoubleDamage));
+
+            if let Some(player) = parent.and_then(|parent| player_ids.get(parent.get()).ok()) {
+                commands.entity(projectile).insert(FiredBy {
+                    player: *player,
+                    origin: projectile_pos,
+                });
+            }
+
+            // shots fired at an enemy under the cursor lock on to it
+            if let (Some(target), Some(_)) = (cursor_target.0, mouse_controlled) {
+                commands
+                    .entity(projectile)
+                    .insert(HomeTowardsEnemies::home_towards(target));
+            }
+
+            if double_damage {
+                commands.entity(projectile).insert(Projectile {
+                    damage: weapon.damage.saturating_mul(2),
+                });
+            }
+
+            // only shots aimed at enemies alert them, enemy tanks should not give away each other
+            if !weapon.targets.contains(&CollisionMask::ENEMY) {
+                continue;
+            }
+
+            // enemies should remember the tank as the threat, not its turret
+            gunfire.send(GunfireEvent {
+                shooter: parent.map_or(shooter, |parent| parent.get()),
+                position: projectile_pos,
+                audible_radius: weapon.audible_radius,
+            });
         }
     }
 }
 
+/// Turns the turret towards the cursor, no faster than the rotation speed of its `Movable`
+#[allow(clippy::type_complexity)]
 pub fn rotate_tank_tower_to_cursor(
-    mut query: Query<(&mut Transform, &GlobalTransform), With<MouseControlled>>,
+    mut query: Query<(&mut Transform, &GlobalTransform, &Movable), With<MouseControlled>>,
     mouse_position_q: Query<&MousePosition2d>,
+    time: Res<Time>,
 ) {
-    let mouse_position = mouse_position_q.single().world_pos;
-    let (mut transform, global_translation) = query.single_mut();
+    let mouse_position = match mouse_position_q.get_single() {
+        Ok(mouse_position) => mouse_position.world_pos,
+        Err(_) => return,
+    };
+
+    // the player tank is gone while waiting to respawn
+    let (mut transform, global_translation, movable) = match query.get_single_mut() {
+        Ok(turret) => turret,
+        Err(_) => return,
+    };
 
     let angle = get_angle_from_transform(&global_translation.compute_transform(), &mouse_position);
 
-    transform.rotate_z(angle);
+    let max_turn = movable.rotation_speed_rad * time.delta_seconds();
+
+    transform.rotate_z(angle.clamp(-max_turn, max_turn));
 }
@@ -2,11 +2,50 @@ use std::cmp::Ordering;
 
 use bevy::{prelude::*, sprite::collide_aabb::collide};
 use bevy_transform_utils::get_angle_from_transform;
+use serde::Deserialize;
 
-use super::{enemy::Enemy, shared::{Health, Lifetime, Collider}, spawner::HomeTowardsEnemies};
+use super::{enemy::Enemy, player::PlayerId, shared::{Health, Lifetime, Collider, DamageEvent, DeathEvent, Movable, Solid}, spawner::HomeTowardsEnemies};
 
 #[derive(Component, Default)]
-pub struct Projectile {}
+pub struct Projectile {
+    pub damage: u16,
+}
+
+/// Sent when a projectile hits a target, before the projectile is despawned at the end of the stage
+pub struct ProjectileHit {
+    pub projectile: Entity,
+    pub target: Entity,
+    pub position: Vec2,
+}
+
+/// Player credited with the hits and kills of a projectile
+#[derive(Component, Clone, Copy)]
+pub struct FiredBy {
+    pub player: PlayerId,
+    /// Where the projectile was fired from, to tell long range hits
+    pub origin: Vec2,
+}
+
+/// Projectile damaging every target in `radius` around the hit
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct Explosive {
+    pub radius: f32,
+    pub damage: u16,
+}
+
+/// Projectile slowing the target it hits
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct SlowOnHit {
+    pub speed_multiplier: f32,
+    pub duration_sec: f32,
+}
+
+/// Target slowed down, `Movable.speed` is restored to `base_speed` once the timer finishes
+#[derive(Component)]
+pub struct Slowed {
+    pub base_speed: i32,
+    pub timer: Timer,
+}
 
 
 #[derive(Component)]
@@ -28,6 +67,11 @@ impl DirectedLinearMove {
     }
 }
 
+/// Rotation under which `DirectedLinearMove::move_forwards_with_speed` moves along `direction`
+pub fn rotation_facing(direction: Vec2) -> Quat {
+    Quat::from_rotation_z(f32::atan2(-direction.y, -direction.x))
+}
+
 #[derive(Bundle, Default)]
 pub struct ProjectileBundle {
     projectile_lifetime: Lifetime,
@@ -73,19 +117,22 @@ pub fn move_linear_particles(
 }
 
 pub fn rotate_homing_entities_towards_nearest_enemies(
-    mut particles: Query<(&mut Transform, &mut DirectedLinearMove), With<HomeTowardsEnemies>>,
+    mut particles: Query<(&mut Transform, &mut DirectedLinearMove, &HomeTowardsEnemies)>,
     enemies: Query<&Transform, (With<Enemy>, Without<HomeTowardsEnemies>)>,
-    time: Res<Time>,
 ) {
-    for (mut entitiy_tr, mut entity_move) in particles.iter_mut() {
-        let nearest_enemy_tr_opt = enemies.iter().min_by(|a, b| {
-            if a.translation.distance(entitiy_tr.translation)
-                < b.translation.distance(entitiy_tr.translation)
-            {
-                Ordering::Less
-            } else {
-                Ordering::Greater
-            }
+    for (mut entitiy_tr, mut entity_move, homing) in particles.iter_mut() {
+        let target_tr_opt = homing.target.and_then(|target| enemies.get(target).ok());
+
+        let nearest_enemy_tr_opt = target_tr_opt.or_else(|| {
+            enemies.iter().min_by(|a, b| {
+                if a.translation.distance(entitiy_tr.translation)
+                    < b.translation.distance(entitiy_tr.translation)
+                {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
         });
 
         if let Some(nearest_enemy_tr) = nearest_enemy_tr_opt {
@@ -99,8 +146,8 @@ pub fn rotate_homing_entities_towards_nearest_enemies(
 
             rotation_angle *= rotation_axis.z;
 
-            entity_move.move_direction.x = -rotation_angle.cos() * time.delta_seconds();
-            entity_move.move_direction.y = -rotation_angle.sin() * time.delta_seconds();
+            entity_move.move_direction.x = -rotation_angle.cos();
+            entity_move.move_direction.y = -rotation_angle.sin();
         }
     }
 }
@@ -120,24 +167,31 @@ pub fn despawn_entity_after_duration_expires(
 }
 
 
+#[allow(clippy::type_complexity)]
 pub fn damage_entities_on_collision(
-    query_particles: Query<(Entity, &Collider, &GlobalTransform, &Sprite), With<Projectile>>,
+    query_particles: Query<(Entity, &Projectile, &Collider, &GlobalTransform)>,
     mut query_targets: Query<
-        (Entity, &mut Health, &Collider, &GlobalTransform, &Sprite),
+        (Entity, &mut Health, &Collider, &GlobalTransform),
         Without<Projectile>,
     >,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut projectile_hits: EventWriter<ProjectileHit>,
+    mut deaths: EventWriter<DeathEvent>,
     mut commands: Commands,
 ) {
-    for (particle, particle_collider, particle_global_transform, particle_sprite) in
+    for (particle, projectile, particle_collider, particle_global_transform) in
         query_particles.iter()
     {
         let particle_translation = particle_global_transform.translation();
 
-        let particle_size = particle_sprite.custom_size.unwrap();
-
-        for (target, mut health, target_collider, target_global_transform, target_sprite) in
+        for (target, mut health, target_collider, target_global_transform) in
             query_targets.iter_mut()
         {
+            // killed by another projectile this frame, it must not die twice
+            if health.is_dead() {
+                continue;
+            }
+
             if particle_collider
                 .collision_mask
                 .iter()
@@ -145,17 +199,32 @@ pub fn damage_entities_on_collision(
             {
                 let target_translation = target_global_transform.translation();
 
-                let target_size = target_sprite.custom_size.unwrap();
-
                 if collide(
                     particle_translation,
-                    particle_size,
+                    particle_collider.size,
                     target_translation,
-                    target_size,
+                    target_collider.size,
                 )
                 .is_some()
                 {
-                    if health.try_apply_damage(25).is_none() {
+                    damage_events.send(DamageEvent {
+                        target,
+                        amount: projectile.damage,
+                    });
+
+                    projectile_hits.send(ProjectileHit {
+                        projectile: particle,
+                        target,
+                        position: particle_translation.truncate(),
+                    });
+
+                    if health.try_apply_damage(projectile.damage).is_none() {
+                        deaths.send(DeathEvent {
+                            entity: target,
+                            position: target_translation.truncate(),
+                            killed_by: Some(particle),
+                        });
+
                         commands.entity(target).despawn_recursive();
                     }
 
@@ -165,3 +234,120 @@ pub fn damage_entities_on_collision(
         }
     }
 }
+
+/// Solids without `Health` cannot be damaged, they just stop the projectiles hitting them
+#[allow(clippy::type_complexity)]
+pub fn stop_projectiles_at_indestructible_solids(
+    projectiles: Query<(Entity, &GlobalTransform, &Collider), With<Projectile>>,
+    solids: Query<(&GlobalTransform, &Collider), (With<Solid>, Without<Health>)>,
+    mut commands: Commands,
+) {
+    for (projectile, projectile_transform, projectile_collider) in projectiles.iter() {
+        let stopped = solids.iter().any(|(solid_transform, solid_collider)| {
+            collide(
+                projectile_transform.translation(),
+                projectile_collider.size,
+                solid_transform.translation(),
+                solid_collider.size,
+            )
+            .is_some()
+        });
+
+        if stopped {
+            commands.entity(projectile).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn explode_projectiles_on_hit(
+    mut projectile_hits: EventReader<ProjectileHit>,
+    explosives: Query<(&Explosive, &Collider), With<Projectile>>,
+    mut query_targets: Query<(Entity, &mut Health, &Collider, &GlobalTransform), Without<Projectile>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut commands: Commands,
+) {
+    for hit in projectile_hits.iter() {
+        let (explosive, projectile_collider) = match explosives.get(hit.projectile) {
+            Ok(explosive) => explosive,
+            Err(_) => continue,
+        };
+
+        for (target, mut health, target_collider, target_global_transform) in query_targets.iter_mut() {
+            // the directly hit target already took the projectile damage
+            if target == hit.target || health.is_dead() {
+                continue;
+            }
+
+            let in_mask = projectile_collider
+                .collision_mask
+                .iter()
+                .any(|e| target_collider.collision_mask.contains(e));
+
+            if !in_mask || target_global_transform.translation().truncate().distance(hit.position) > explosive.radius {
+                continue;
+            }
+
+            damage_events.send(DamageEvent {
+                target,
+                amount: explosive.damage,
+            });
+
+            if health.try_apply_damage(explosive.damage).is_none() {
+                deaths.send(DeathEvent {
+                    entity: target,
+                    position: target_global_transform.translation().truncate(),
+                    killed_by: Some(hit.projectile),
+                });
+
+                commands.entity(target).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub fn slow_targets_on_hit(
+    mut projectile_hits: EventReader<ProjectileHit>,
+    slows: Query<&SlowOnHit, With<Projectile>>,
+    mut targets: Query<(&mut Movable, Option<&mut Slowed>)>,
+    mut commands: Commands,
+) {
+    for hit in projectile_hits.iter() {
+        let slow = match slows.get(hit.projectile) {
+            Ok(slow) => slow,
+            Err(_) => continue,
+        };
+
+        let (mut movable, slowed) = match targets.get_mut(hit.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        match slowed {
+            Some(mut slowed) => slowed.timer = Timer::from_seconds(slow.duration_sec, false),
+            None => {
+                commands.entity(hit.target).insert(Slowed {
+                    base_speed: movable.speed,
+                    timer: Timer::from_seconds(slow.duration_sec, false),
+                });
+
+                movable.speed = (movable.speed as f32 * slow.speed_multiplier).round() as i32;
+            }
+        }
+    }
+}
+
+pub fn expire_slows(
+    mut slowed_targets: Query<(Entity, &mut Slowed, &mut Movable)>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut slowed, mut movable) in slowed_targets.iter_mut() {
+        if slowed.timer.tick(time.delta()).finished() {
+            movable.speed = slowed.base_speed;
+
+            commands.entity(entity).remove::<Slowed>();
+        }
+    }
+}
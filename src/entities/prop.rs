@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    ai::perception::BlocksSight,
+    layers::ZLayers,
+    navigation::NavBlocker,
+    projectiles::{Explosive, ProjectileHit},
+    shared::{
+        CollisionMask, DamageEvent, DeathEvent, DisplayName, EntitySharedBundle, Health, Solid,
+    },
+    sprites::SpriteDefinitions,
+};
+
+/// Share of the health left below which a prop shows its damaged variant
+const DAMAGED_HEALTH_FRACTION: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum PropKind {
+    Tree,
+    Crate,
+    Fence,
+    Building,
+}
+
+impl PropKind {
+    pub const ALL: [PropKind; 4] = [
+        PropKind::Tree,
+        PropKind::Crate,
+        PropKind::Fence,
+        PropKind::Building,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PropKind::Tree => "Tree",
+            PropKind::Crate => "Crate",
+            PropKind::Fence => "Fence",
+            PropKind::Building => "Building",
+        }
+    }
+
+    fn health(&self) -> u16 {
+        match self {
+            PropKind::Tree => 60,
+            PropKind::Crate => 30,
+            PropKind::Fence => 40,
+            PropKind::Building => 400,
+        }
+    }
+
+    /// Sprite definition path, the sprite is tinted by the state of the prop
+    pub fn sprite_definition(&self) -> &'static str {
+        match self {
+            PropKind::Tree => "sprites/tree.sprite.ron",
+            PropKind::Crate => "sprites/crate.sprite.ron",
+            PropKind::Fence => "sprites/fence.sprite.ron",
+            PropKind::Building => "sprites/building.sprite.ron",
+        }
+    }
+
+    /// Fences and crates are low enough to look over
+    fn blocks_sight(&self) -> bool {
+        matches!(self, PropKind::Tree | PropKind::Building)
+    }
+
+    fn color(&self, state: PropState) -> Color {
+        match (self, state) {
+            (PropKind::Tree, PropState::Intact) => Color::rgb(0.1, 0.35, 0.1),
+            (PropKind::Tree, PropState::Damaged) => Color::rgb(0.3, 0.35, 0.1),
+            (PropKind::Tree, PropState::Destroyed) => Color::rgb(0.3, 0.2, 0.1),
+            (PropKind::Crate, PropState::Intact) => Color::rgb(0.6, 0.45, 0.25),
+            (PropKind::Crate, PropState::Damaged) => Color::rgb(0.45, 0.33, 0.18),
+            (PropKind::Crate, PropState::Destroyed) => Color::rgb(0.35, 0.27, 0.17),
+            (PropKind::Fence, PropState::Intact) => Color::rgb(0.55, 0.5, 0.4),
+            (PropKind::Fence, PropState::Damaged) => Color::rgb(0.42, 0.38, 0.3),
+            (PropKind::Fence, PropState::Destroyed) => Color::rgb(0.33, 0.3, 0.25),
+            (PropKind::Building, PropState::Intact) => Color::rgb(0.55, 0.35, 0.3),
+            (PropKind::Building, PropState::Damaged) => Color::rgb(0.4, 0.27, 0.24),
+            (PropKind::Building, PropState::Destroyed) => Color::rgb(0.25, 0.22, 0.2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PropState {
+    Intact,
+    Damaged,
+    Destroyed,
+}
+
+/// Destructible scenery blocking movement and shots until it is destroyed
+#[derive(Component)]
+pub struct Prop {
+    pub kind: PropKind,
+    pub state: PropState,
+}
+
+/// Remains of a destroyed prop, nothing is blocked by it
+#[derive(Component)]
+pub struct Rubble {
+    pub kind: PropKind,
+}
+
+pub fn spawn_prop(
+    kind: PropKind,
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let definition = sprites.get(kind.sprite_definition());
+
+    let mut shared = EntitySharedBundle::from_definition(kind.name(), definition);
+
+    shared.sprite.sprite.color = kind.color(PropState::Intact);
+    shared.sprite.transform.translation = position.extend(layers.units);
+
+    let mut prop = commands.spawn_bundle(shared);
+
+    prop.insert(Prop {
+        kind,
+        state: PropState::Intact,
+    })
+    .insert(Solid)
+    .insert(Health::new(kind.health()))
+    .insert(definition.collider(vec![CollisionMask::PLAYER, CollisionMask::ENEMY]))
+    .insert(NavBlocker);
+
+    if kind.blocks_sight() {
+        prop.insert(BlocksSight);
+    }
+
+    prop.id()
+}
+
+fn spawn_rubble(
+    kind: PropKind,
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) {
+    let definition = sprites.get(kind.sprite_definition());
+
+    let mut shared = EntitySharedBundle::from_definition(kind.name(), definition);
+
+    shared.name = DisplayName(format!("{} Rubble", kind.name()));
+    shared.sprite.sprite.color = kind.color(PropState::Destroyed);
+    shared.sprite.transform.translation = position.extend(layers.decals);
+
+    commands.spawn_bundle(shared).insert(Rubble { kind });
+}
+
+#[allow(clippy::type_complexity)]
+pub fn show_damaged_props(mut props: Query<(&mut Prop, &Health, &mut Sprite), Changed<Health>>) {
+    for (mut prop, health, mut sprite) in props.iter_mut() {
+        let health_fraction = health.current_health as f32 / health.max_health as f32;
+
+        let state = if health_fraction < DAMAGED_HEALTH_FRACTION {
+            PropState::Damaged
+        } else {
+            PropState::Intact
+        };
+
+        if prop.state != state {
+            prop.state = state;
+            sprite.color = prop.kind.color(state);
+        }
+    }
+}
+
+/// Explosions flatten every prop in their radius, whatever health it has left. Runs after the
+/// other damage systems, so props they already destroyed this frame are skipped.
+pub fn clear_props_in_explosions(
+    mut projectile_hits: EventReader<ProjectileHit>,
+    explosives: Query<&Explosive>,
+    mut props: Query<(Entity, &mut Health, &GlobalTransform), With<Prop>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut commands: Commands,
+) {
+    for hit in projectile_hits.iter() {
+        let explosive = match explosives.get(hit.projectile) {
+            Ok(explosive) => explosive,
+            Err(_) => continue,
+        };
+
+        for (prop, mut health, transform) in props.iter_mut() {
+            let position = transform.translation().truncate();
+
+            if health.is_dead() || position.distance(hit.position) > explosive.radius {
+                continue;
+            }
+
+            damage_events.send(DamageEvent {
+                target: prop,
+                amount: health.current_health,
+            });
+
+            health.current_health = 0;
+
+            deaths.send(DeathEvent {
+                entity: prop,
+                position,
+                killed_by: Some(hit.projectile),
+            });
+
+            commands.entity(prop).despawn_recursive();
+        }
+    }
+}
+
+/// Leaves the destroyed variant behind, the prop itself is gone so it no longer blocks anything
+pub fn leave_rubble_of_destroyed_props(
+    mut deaths: EventReader<DeathEvent>,
+    props: Query<&Prop>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut commands: Commands,
+) {
+    for death in deaths.iter() {
+        if let Ok(prop) = props.get(death.entity) {
+            info!("{} destroyed", prop.kind.name());
+
+            spawn_rubble(prop.kind, death.position, &layers, &sprites, &mut commands);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use super::{
+    build::Structure,
+    economy::{Bank, TransactionReason},
+    hq::{Headquarters, HqStatus},
+    player::{PlayerAction, PlayerControlled},
+    shared::{DamageEvent, Health},
+};
+
+/// Distance from the player tank within which structures can be repaired
+const REPAIR_RANGE: f32 = 150.0;
+
+const REPAIR_HEALTH_PER_SEC: f32 = 25.0;
+
+/// Currency charged for every repaired health point
+const REPAIR_COST_PER_HEALTH: u32 = 1;
+
+/// Tank being used to repair structures, its cannon cannot fire while a repair is in progress
+#[derive(Component, Default)]
+pub struct RepairTool {
+    pub target: Option<Entity>,
+    /// Health repaired this far that did not add up to a whole point yet
+    progress: f32,
+}
+
+impl RepairTool {
+    pub fn is_repairing(&self) -> bool {
+        self.target.is_some()
+    }
+}
+
+/// Slowly restores the tank's health when it has not been damaged for a while
+#[derive(Component)]
+pub struct SelfRepair {
+    pub health_per_sec: f32,
+    pub out_of_combat_timer: Timer,
+    progress: f32,
+}
+
+impl Default for SelfRepair {
+    fn default() -> Self {
+        Self {
+            health_per_sec: 4.0,
+            out_of_combat_timer: Timer::from_seconds(5.0, false),
+            progress: 0.0,
+        }
+    }
+}
+
+impl SelfRepair {
+    /// Heals `health` once the tank went long enough without being damaged, a hit starts the wait
+    /// over. Dead tanks stay dead.
+    pub fn tick(&mut self, delta: Duration, damaged: bool, health: &mut Health) {
+        if damaged {
+            self.out_of_combat_timer.reset();
+            self.progress = 0.0;
+
+            return;
+        }
+
+        // the frame that ends the wait is still part of it
+        let waited = self.out_of_combat_timer.finished();
+
+        self.out_of_combat_timer.tick(delta);
+
+        if !waited {
+            return;
+        }
+
+        if health.is_dead() || !health.is_damaged() {
+            return;
+        }
+
+        self.progress += self.health_per_sec * delta.as_secs_f32();
+
+        let points = take_whole_points(&mut self.progress);
+
+        health.heal(points);
+    }
+}
+
+/// Damaged structures and the headquarters, anything the repair tool works on
+type Repairables<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Health,
+        &'static GlobalTransform,
+        Option<&'static Headquarters>,
+    ),
+    (Or<(With<Structure>, With<Headquarters>)>, Without<PlayerControlled>),
+>;
+
+/// Takes the whole health points out of the accumulated progress
+fn take_whole_points(progress: &mut f32) -> u16 {
+    let points = progress.floor();
+
+    *progress -= points;
+
+    return points as u16;
+}
+
+fn nearest_damaged_in_range(
+    position: Vec2,
+    repairables: &Repairables,
+) -> Option<Entity> {
+    repairables
+        .iter()
+        .filter(|(_, health, _, _)| health.is_damaged())
+        .map(|(entity, _, transform, _)| {
+            (entity, transform.translation().truncate().distance(position))
+        })
+        .filter(|(_, distance)| *distance <= REPAIR_RANGE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+/// Repairs the nearest damaged structure or headquarters in range while the repair action is held
+#[allow(clippy::type_complexity)]
+pub fn repair_structures(
+    mut players: Query<
+        (&ActionState<PlayerAction>, &GlobalTransform, &mut RepairTool),
+        With<PlayerControlled>,
+    >,
+    mut repairables: Repairables,
+    mut hq_status: ResMut<HqStatus>,
+    mut bank: Bank,
+    time: Res<Time>,
+) {
+    for (action_state, player_transform, mut repair_tool) in players.iter_mut() {
+        if !action_state.pressed(PlayerAction::Repair) {
+            repair_tool.target = None;
+            repair_tool.progress = 0.0;
+
+            continue;
+        }
+
+        let position = player_transform.translation().truncate();
+
+        let target = nearest_damaged_in_range(position, &repairables);
+
+        if target != repair_tool.target {
+            repair_tool.target = target;
+            repair_tool.progress = 0.0;
+        }
+
+        let (target, mut health, _, headquarters) =
+            match target.and_then(|target| repairables.get_mut(target).ok()) {
+                Some(repairable) => repairable,
+                None => continue,
+            };
+
+        repair_tool.progress += REPAIR_HEALTH_PER_SEC * time.delta_seconds();
+
+        let points = take_whole_points(&mut repair_tool.progress)
+            .min(health.max_health.saturating_sub(health.current_health));
+
+        if points == 0 {
+            continue;
+        }
+
+        let cost = points as u32 * REPAIR_COST_PER_HEALTH;
+
+        if let Err(error) = bank.try_purchase(cost, TransactionReason::Repair) {
+            debug!("Cannot afford repairing {:?}: {:?}", target, error);
+
+            repair_tool.target = None;
+
+            continue;
+        }
+
+        health.heal(points);
+
+        if headquarters.is_some() {
+            hq_status.health = health.current_health;
+        }
+    }
+}
+
+pub fn self_repair_out_of_combat(
+    mut damage_events: EventReader<DamageEvent>,
+    mut tanks: Query<(Entity, &mut SelfRepair, &mut Health)>,
+    time: Res<Time>,
+) {
+    let damaged: Vec<Entity> = damage_events.iter().map(|damage| damage.target).collect();
+
+    for (entity, mut self_repair, mut health) in tanks.iter_mut() {
+        self_repair.tick(time.delta(), damaged.contains(&entity), &mut health);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damaged_health() -> Health {
+        let mut health = Health::new(100);
+
+        health.try_apply_damage(50);
+
+        return health;
+    }
+
+    #[test]
+    fn self_repair_heals_once_out_of_combat() {
+        let mut self_repair = SelfRepair::default();
+        let mut health = damaged_health();
+
+        self_repair.tick(Duration::from_secs(5), false, &mut health);
+        self_repair.tick(Duration::from_secs(1), false, &mut health);
+
+        assert_eq!(health.current_health, 54);
+    }
+
+    #[test]
+    fn self_repair_waits_while_in_combat() {
+        let mut self_repair = SelfRepair::default();
+        let mut health = damaged_health();
+
+        self_repair.tick(Duration::from_secs(4), false, &mut health);
+        self_repair.tick(Duration::from_secs(1), true, &mut health);
+        self_repair.tick(Duration::from_secs(4), false, &mut health);
+
+        assert_eq!(health.current_health, 50);
+    }
+
+    #[test]
+    fn self_repair_stops_at_max_health() {
+        let mut self_repair = SelfRepair::default();
+        let mut health = Health::new(100);
+
+        health.try_apply_damage(1);
+
+        self_repair.tick(Duration::from_secs(5), false, &mut health);
+        self_repair.tick(Duration::from_secs(10), false, &mut health);
+
+        assert_eq!(health.current_health, 100);
+    }
+}
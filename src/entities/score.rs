@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use super::{
+    enemy::Enemy,
+    hud::UiFont,
+    player::{PlayerId, LOCAL_PLAYER},
+    projectiles::{FiredBy, ProjectileHit},
+    shared::{DamageEvent, DeathEvent, Health},
+};
+
+/// Kills within this time of each other keep the combo going
+const COMBO_WINDOW_SEC: f32 = 3.0;
+const MAX_MULTIPLIER: u32 = 8;
+
+/// Bonus for every kill beyond the first one scored by the same projectile
+const MULTI_KILL_BONUS: u64 = 250;
+
+/// Hits from at least this far away from where the projectile was fired count as long range
+const LONG_RANGE_DISTANCE: f32 = 500.0;
+const LONG_RANGE_BONUS: u64 = 50;
+
+/// Points for killing the entity, before the combo multiplier
+#[derive(Component, Clone, Copy)]
+pub struct ScoreValue(pub u32);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScoreReason {
+    Kill,
+    MultiKill { kills: u32 },
+    LongRangeHit,
+}
+
+/// Every change of a player's score, for the HUD to pop up
+pub struct ScoreEvent {
+    pub player: PlayerId,
+    pub points: u64,
+    pub reason: ScoreReason,
+    pub total: u64,
+}
+
+pub struct PlayerScore {
+    pub score: u64,
+    pub kills: u32,
+    pub multiplier: u32,
+    pub best_multiplier: u32,
+    pub multi_kills: u32,
+    pub long_range_hits: u32,
+    /// Drops the multiplier back to 1 when it finishes before the next kill
+    pub combo_timer: Timer,
+}
+
+impl Default for PlayerScore {
+    fn default() -> Self {
+        Self {
+            score: 0,
+            kills: 0,
+            multiplier: 1,
+            best_multiplier: 1,
+            multi_kills: 0,
+            long_range_hits: 0,
+            combo_timer: Timer::from_seconds(COMBO_WINDOW_SEC, false),
+        }
+    }
+}
+
+impl PlayerScore {
+    /// Adds the kill at the current multiplier and raises the multiplier for the next one
+    fn register_kill(&mut self, points: u32) -> u64 {
+        let awarded = points as u64 * self.multiplier as u64;
+
+        self.score += awarded;
+        self.kills += 1;
+
+        self.multiplier = (self.multiplier + 1).min(MAX_MULTIPLIER);
+        self.best_multiplier = self.best_multiplier.max(self.multiplier);
+
+        self.combo_timer.reset();
+
+        return awarded;
+    }
+
+    fn reset_combo(&mut self) {
+        self.multiplier = 1;
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "Score {}\n{} kills\nBest multiplier x{}\n{} multi-kills\n{} long range hits",
+            self.score, self.kills, self.best_multiplier, self.multi_kills, self.long_range_hits
+        )
+    }
+}
+
+/// Scores of every player for the whole run, read by the HUD and the end of run summary
+#[derive(Default)]
+pub struct Scoreboard {
+    pub players: HashMap<PlayerId, PlayerScore>,
+}
+
+impl Scoreboard {
+    pub fn get(&self, player: PlayerId) -> Option<&PlayerScore> {
+        self.players.get(&player)
+    }
+
+    fn entry(&mut self, player: PlayerId) -> &mut PlayerScore {
+        self.players.entry(player).or_default()
+    }
+}
+
+/// Score line of the local player in the corner of the screen
+#[derive(Component)]
+pub struct ScoreText;
+
+pub fn award_kill_scores(
+    mut deaths: EventReader<DeathEvent>,
+    victims: Query<&ScoreValue, With<Enemy>>,
+    projectiles: Query<&FiredBy>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut score_events: EventWriter<ScoreEvent>,
+) {
+    let mut kills_by_projectile: HashMap<Entity, (PlayerId, u32)> = HashMap::new();
+
+    // an enemy reported dead twice in a frame is still a single kill
+    let mut scored = HashSet::new();
+
+    for death in deaths.iter() {
+        if !scored.insert(death.entity) {
+            continue;
+        }
+
+        let value = match victims.get(death.entity) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        let projectile = match death.killed_by {
+            Some(projectile) => projectile,
+            None => continue,
+        };
+
+        let fired_by = match projectiles.get(projectile) {
+            Ok(fired_by) => fired_by,
+            Err(_) => continue,
+        };
+
+        let player_score = scoreboard.entry(fired_by.player);
+
+        let points = player_score.register_kill(value.0);
+
+        score_events.send(ScoreEvent {
+            player: fired_by.player,
+            points,
+            reason: ScoreReason::Kill,
+            total: player_score.score,
+        });
+
+        kills_by_projectile
+            .entry(projectile)
+            .or_insert((fired_by.player, 0))
+            .1 += 1;
+    }
+
+    for (player, kills) in kills_by_projectile.into_values() {
+        if kills < 2 {
+            continue;
+        }
+
+        let player_score = scoreboard.entry(player);
+
+        let points = MULTI_KILL_BONUS * (kills - 1) as u64;
+
+        player_score.score += points;
+        player_score.multi_kills += 1;
+
+        info!("Multi-kill of {} for {:?}", kills, player);
+
+        score_events.send(ScoreEvent {
+            player,
+            points,
+            reason: ScoreReason::MultiKill { kills },
+            total: player_score.score,
+        });
+    }
+}
+
+pub fn award_long_range_hits(
+    mut projectile_hits: EventReader<ProjectileHit>,
+    projectiles: Query<&FiredBy>,
+    enemies: Query<(), With<Enemy>>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut score_events: EventWriter<ScoreEvent>,
+) {
+    for hit in projectile_hits.iter() {
+        let fired_by = match projectiles.get(hit.projectile) {
+            Ok(fired_by) => fired_by,
+            Err(_) => continue,
+        };
+
+        if enemies.get(hit.target).is_err()
+            || fired_by.origin.distance(hit.position) < LONG_RANGE_DISTANCE
+        {
+            continue;
+        }
+
+        let player_score = scoreboard.entry(fired_by.player);
+
+        player_score.score += LONG_RANGE_BONUS;
+        player_score.long_range_hits += 1;
+
+        score_events.send(ScoreEvent {
+            player: fired_by.player,
+            points: LONG_RANGE_BONUS,
+            reason: ScoreReason::LongRangeHit,
+            total: player_score.score,
+        });
+    }
+}
+
+/// Taking damage breaks the combo, hits absorbed while invulnerable do not count
+pub fn reset_combo_on_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    players: Query<(&PlayerId, &Health)>,
+    mut scoreboard: ResMut<Scoreboard>,
+) {
+    for damage in damage_events.iter() {
+        let player = match players.get(damage.target) {
+            Ok((player, health)) if !health.invulnerable => *player,
+            _ => continue,
+        };
+
+        scoreboard.entry(player).reset_combo();
+    }
+}
+
+pub fn expire_combos(mut scoreboard: ResMut<Scoreboard>, time: Res<Time>) {
+    for player_score in scoreboard.players.values_mut() {
+        if player_score.multiplier > 1 && player_score.combo_timer.tick(time.delta()).finished() {
+            player_score.reset_combo();
+        }
+    }
+}
+
+pub fn spawn_score_hud(font: Res<UiFont>, mut commands: Commands) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section("Score 0", font.style(24.0, Color::WHITE)).with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(12.0),
+                    top: Val::Px(12.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        )
+        .insert(ScoreText);
+}
+
+pub fn update_score_hud(
+    scoreboard: Res<Scoreboard>,
+    mut texts: Query<&mut Text, With<ScoreText>>,
+) {
+    let player_score = match scoreboard.get(LOCAL_PLAYER) {
+        Some(player_score) => player_score,
+        None => return,
+    };
+
+    let line = format!(
+        "Score {}  x{}  {} kills",
+        player_score.score, player_score.multiplier, player_score.kills
+    );
+
+    for mut text in texts.iter_mut() {
+        // rewriting the same line would lay the text out again every frame
+        if text.sections[0].value != line {
+            text.sections[0].value = line.clone();
+        }
+    }
+}
+
+pub fn show_run_summary(scoreboard: Res<Scoreboard>, font: Res<UiFont>, mut commands: Commands) {
+    for (player, player_score) in scoreboard.players.iter() {
+        info!(
+            "{:?}: score {}, {} kills, best multiplier x{}, {} multi-kills, {} long range hits",
+            player,
+            player_score.score,
+            player_score.kills,
+            player_score.best_multiplier,
+            player_score.multi_kills,
+            player_score.long_range_hits
+        );
+    }
+
+    let summary = scoreboard
+        .get(LOCAL_PLAYER)
+        .map_or_else(|| PlayerScore::default().summary(), |player_score| player_score.summary());
+
+    commands.spawn_bundle(
+        TextBundle::from_section(summary, font.style(32.0, Color::WHITE)).with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Percent(40.0),
+                top: Val::Percent(35.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kills_raise_the_multiplier_for_the_next_one() {
+        let mut player_score = PlayerScore::default();
+
+        assert_eq!(player_score.register_kill(100), 100);
+        assert_eq!(player_score.register_kill(100), 200);
+        assert_eq!(player_score.register_kill(100), 300);
+
+        assert_eq!(player_score.score, 600);
+        assert_eq!(player_score.kills, 3);
+        assert_eq!(player_score.best_multiplier, 4);
+    }
+
+    #[test]
+    fn multiplier_is_capped() {
+        let mut player_score = PlayerScore::default();
+
+        for _ in 0..20 {
+            player_score.register_kill(10);
+        }
+
+        assert_eq!(player_score.multiplier, MAX_MULTIPLIER);
+        assert_eq!(player_score.register_kill(10), 10 * MAX_MULTIPLIER as u64);
+    }
+
+    #[test]
+    fn reset_combo_keeps_the_best_multiplier() {
+        let mut player_score = PlayerScore::default();
+
+        player_score.register_kill(10);
+        player_score.register_kill(10);
+        player_score.reset_combo();
+
+        assert_eq!(player_score.multiplier, 1);
+        assert_eq!(player_score.best_multiplier, 3);
+        assert_eq!(player_score.register_kill(10), 10);
+    }
+}
@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use super::sprites::SpriteDefinition;
+
 #[derive(Component, Default)]
 pub struct DisplayName(pub String);
 
@@ -28,17 +30,15 @@ pub struct EntitySharedBundle {
 
 impl Default for EntitySharedBundle {
     fn default() -> Self {
-        let default_sprite = Sprite {
-            custom_size: Some(Vec2 { x: 64.0, y: 64.0 }), // FIXME asset sizes should be in a bevy asset
-            ..Default::default()
-        };
+        EntitySharedBundle::from_definition("Unnamed Entity", &SpriteDefinition::default())
+    }
+}
 
+impl EntitySharedBundle {
+    pub fn from_definition(name: &str, definition: &SpriteDefinition) -> Self {
         Self {
-            name: DisplayName("Unnamed Entity".to_string()),
-            sprite: SpriteBundle {
-                sprite: default_sprite,
-                ..Default::default()
-            },
+            name: DisplayName(name.to_string()),
+            sprite: definition.sprite_bundle(),
         }
     }
 }
@@ -47,6 +47,10 @@ impl Default for EntitySharedBundle {
 pub struct Health {
     pub max_health: u16,
     pub current_health: u16,
+    /// Flat reduction of every hit, a hit always deals at least 1 damage
+    pub armor: u16,
+    /// Hits are ignored while set, e.g. while a shield is up
+    pub invulnerable: bool,
 }
 
 impl Health {
@@ -54,10 +58,23 @@ impl Health {
         Health {
             max_health: initial_health,
             current_health: initial_health,
+            armor: 0,
+            invulnerable: false,
         }
     }
 
     pub fn try_apply_damage(&mut self, damage: u16) -> Option<u16> {
+        if self.invulnerable {
+            return Some(self.current_health);
+        }
+
+        // armored entities always take at least 1 damage, so armor cannot make them invulnerable
+        let damage = if self.armor > 0 {
+            damage.saturating_sub(self.armor).max(1)
+        } else {
+            damage
+        };
+
         if let Some(new_health) = self.current_health.checked_sub(damage) {
             self.current_health = new_health;
 
@@ -66,8 +83,42 @@ impl Health {
             }
         }
 
+        // nothing is left after a lethal hit, so later hits in the same frame see the entity as dead
+        self.current_health = 0;
+
         return None;
     }
+
+    /// Dead entities are only waiting for their despawn, damage systems skip them
+    pub fn is_dead(&self) -> bool {
+        self.current_health == 0
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.current_health < self.max_health
+    }
+
+    /// Restores up to `amount` health without going over the maximum, returns how much was restored
+    pub fn heal(&mut self, amount: u16) -> u16 {
+        let healed = amount.min(self.max_health.saturating_sub(self.current_health));
+
+        self.current_health += healed;
+
+        return healed;
+    }
+}
+
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u16,
+}
+
+/// Sent when damage destroys an entity, before it is despawned at the end of the stage
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub position: Vec2,
+    /// Projectile that landed the killing blow, if any
+    pub killed_by: Option<Entity>,
 }
 
 #[derive(Component, Default)]
@@ -84,22 +135,87 @@ impl Lifetime {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CollisionMask {
     PLAYER,
     ENEMY,
 }
 
+/// Box hit by projectiles, sized like the sprite definition of the entity where it has one
 #[derive(Component)]
 pub struct Collider {
     pub collision_mask: Vec<CollisionMask>,
+    pub size: Vec2,
 }
 
 impl Collider {
-    pub fn new(collision_mask: Vec<CollisionMask>) -> Self {
-        Collider { collision_mask }
+    pub fn new(collision_mask: Vec<CollisionMask>, size: Vec2) -> Self {
+        Collider {
+            collision_mask,
+            size,
+        }
     }
 }
 
 #[derive(Component, Default)]
-pub struct MouseControlled;
\ No newline at end of file
+pub struct MouseControlled;
+
+/// Marks entities that movers cannot pass through
+#[derive(Component, Default)]
+pub struct Solid;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_lethal_damage_returns_remaining_health() {
+        let mut health = Health::new(100);
+
+        assert_eq!(health.try_apply_damage(30), Some(70));
+        assert!(!health.is_dead());
+    }
+
+    #[test]
+    fn overshooting_hit_leaves_no_health() {
+        let mut health = Health::new(20);
+
+        assert_eq!(health.try_apply_damage(50), None);
+        assert_eq!(health.current_health, 0);
+        assert!(health.is_dead());
+    }
+
+    #[test]
+    fn exact_lethal_hit_kills() {
+        let mut health = Health::new(20);
+
+        assert_eq!(health.try_apply_damage(20), None);
+        assert!(health.is_dead());
+    }
+
+    #[test]
+    fn armor_reduces_damage_down_to_one() {
+        let mut health = Health::new(100);
+
+        health.armor = 4;
+
+        assert_eq!(health.try_apply_damage(10), Some(94));
+        assert_eq!(health.try_apply_damage(3), Some(93));
+    }
+
+    #[test]
+    fn zero_damage_hits_unarmored_entities_for_nothing() {
+        let mut health = Health::new(100);
+
+        assert_eq!(health.try_apply_damage(0), Some(100));
+    }
+
+    #[test]
+    fn invulnerable_entities_ignore_hits() {
+        let mut health = Health::new(20);
+
+        health.invulnerable = true;
+
+        assert_eq!(health.try_apply_damage(50), Some(20));
+        assert!(!health.is_dead());
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    difficulty::Difficulty,
+    economy::Bounty,
+    enemy::EnemyArchetype,
+    layers::ZLayers,
+    shared::{CollisionMask, DisplayName, EntitySharedBundle, Health},
+    sprites::{SpriteDefinitions, SPAWN_POINT_SPRITE},
+};
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SpawnTableEntry {
+    pub archetype: EnemyArchetype,
+    pub weight: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+pub enum SpawnPointActivation {
+    Always,
+    FromWave(u32),
+    PlayerWithin(f32),
+}
+
+#[derive(Component)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub spawn_table: Vec<SpawnTableEntry>,
+    pub cooldown: Timer,
+    pub max_alive: usize,
+    pub activation: SpawnPointActivation,
+    /// Waypoints enemies from this spawn point follow before heading for the headquarters
+    pub path: Vec<Vec2>,
+}
+
+/// Links a spawned enemy to the spawn point it came out of, used to enforce `SpawnPoint.max_alive`
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+impl SpawnPoint {
+    pub fn new(
+        name: &str,
+        spawn_table: Vec<SpawnTableEntry>,
+        cooldown_sec: f32,
+        max_alive: usize,
+        activation: SpawnPointActivation,
+    ) -> Self {
+        let mut cooldown = Timer::from_seconds(cooldown_sec, false);
+
+        cooldown.tick(cooldown.duration()); // first spawn is not delayed
+
+        SpawnPoint {
+            name: name.to_string(),
+            spawn_table,
+            cooldown,
+            max_alive,
+            activation,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn with_path(mut self, path: Vec<Vec2>) -> Self {
+        self.path = path;
+
+        self
+    }
+
+    pub fn is_active(&self, wave_number: u32, position: Vec2, player_position: Option<Vec2>) -> bool {
+        match self.activation {
+            SpawnPointActivation::Always => true,
+            SpawnPointActivation::FromWave(first_wave) => wave_number >= first_wave,
+            SpawnPointActivation::PlayerWithin(distance) => player_position
+                .map(|player_position| player_position.distance(position) <= distance)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Whether the spawn point may still become active while `wave_number` runs and has anything to spawn
+    pub fn can_spawn_in_wave(&self, wave_number: u32) -> bool {
+        let waits_for_later_wave = match self.activation {
+            SpawnPointActivation::FromWave(first_wave) => wave_number < first_wave,
+            _ => false,
+        };
+
+        let has_enemies = self.spawn_table.iter().any(|entry| entry.weight > 0);
+
+        return !waits_for_later_wave && has_enemies;
+    }
+
+    pub fn roll_archetype(&self) -> Option<EnemyArchetype> {
+        let total_weight: u32 = self.spawn_table.iter().map(|entry| entry.weight).sum();
+
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut roll = fastrand::u32(0..total_weight);
+
+        for entry in self.spawn_table.iter() {
+            if roll < entry.weight {
+                return Some(entry.archetype);
+            }
+
+            roll -= entry.weight;
+        }
+
+        return None;
+    }
+}
+
+/// Spawn points are pads on the ground the enemies they spawn drive over
+pub fn spawn_spawn_point(
+    spawn_point: SpawnPoint,
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let definition = sprites.get(SPAWN_POINT_SPRITE);
+
+    let mut shared = EntitySharedBundle::from_definition("Spawn point", definition);
+
+    shared.name = DisplayName(format!("Spawn point {}", spawn_point.name));
+    shared.sprite.transform = Transform::from_translation(position.extend(layers.decals));
+
+    commands
+        .spawn_bundle(shared)
+        .insert(spawn_point)
+        .insert(definition.collider(vec![CollisionMask::ENEMY]))
+        .insert(Health::new(400))
+        .insert(Bounty(50))
+        .id()
+}
+
+/// Cooldowns run faster or slower with the spawn rate of the difficulty
+pub fn tick_spawn_point_cooldowns(
+    mut spawn_points: Query<&mut SpawnPoint>,
+    difficulty: Res<Difficulty>,
+    time: Res<Time>,
+) {
+    let delta = time.delta().mul_f32(difficulty.modifiers().spawn_rate);
+
+    for mut spawn_point in spawn_points.iter_mut() {
+        spawn_point.cooldown.tick(delta);
+    }
+}
+
+pub fn log_destroyed_spawn_points(
+    destroyed: RemovedComponents<SpawnPoint>,
+    remaining: Query<&SpawnPoint>,
+) {
+    for _ in destroyed.iter() {
+        info!("Spawn point destroyed, {} remaining", remaining.iter().count());
+    }
+}
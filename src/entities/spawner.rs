@@ -1,32 +1,129 @@
-use bevy::{prelude::*, sprite::Anchor};
-use bevy_rand_utils::RandomFromRange;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
 
 use crate::entities::player_input::get_input_manager;
 
 use super::{
-    enemy::{Enemy, EnemyBundle},
-    player::{PlayerControlled, TankBundle},
+    ai::{
+        behavior_tree::{BehaviorTreeAgent, Blackboard},
+        enemy_ai::Idle,
+        perception::PerceptionTarget,
+        tank_ai::{EnemyTankAi, TurretAim},
+    },
+    difficulty::DifficultyModifiers,
+    economy::Bounty,
+    enemy::{Enemy, EnemyArchetype, EnemyBundle},
+    fog::{VisionSource, PLAYER_VISION_RADIUS},
+    layers::ZLayers,
+    loot::{LootDropper, PowerUps},
+    player::{
+        PlayerAction, PlayerControlled, TankBundle, TankTurret, LOCAL_PLAYER, TANK_MAX_HEALTH,
+        TURRET_ROTATION_SPEED_DEG,
+    },
     projectiles::{DirectedLinearMove, Projectile},
-    shared::{Collider, CollisionMask, DisplayName, Health, Lifetime, MouseControlled},
+    score::ScoreValue,
+    repair::{RepairTool, SelfRepair},
+    shared::{CollisionMask, DisplayName, Health, Lifetime, MouseControlled, Movable},
+    sprites::{SpriteDefinition, SpriteDefinitions, TANK_BODY_SPRITE, TANK_TURRET_SPRITE},
+    weapons::{Ammo, Weapon},
 };
 
-pub fn spawn_enemies_on_demand(mut commands: Commands, query: Query<&Enemy>) {
-    if query.iter().count() < 1 {
-        spawn_enemy("Enemy1", &mut commands);
+const PLAYER_MAX_AMMO: u32 = 60;
+
+pub fn spawn_enemy(
+    archetype: EnemyArchetype,
+    position: Vec2,
+    modifiers: &DifficultyModifiers,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    if archetype == EnemyArchetype::Tank {
+        return spawn_enemy_tank(position, modifiers, layers, sprites, commands);
     }
-}
 
-pub fn spawn_enemy(name: &str, commands: &mut Commands) {
-    let enemy = EnemyBundle::new(name);
+    let definition = sprites.get(archetype.sprite_definition());
 
-    commands
-        .spawn_bundle(enemy)
-        .insert(Collider::new(vec![CollisionMask::ENEMY]))
-        .insert(Health::new(100))
+    let mut enemy = EnemyBundle::from_archetype(archetype, sprites);
+
+    enemy.movable.speed = modifiers.scale_speed(enemy.movable.speed);
+
+    let mut enemy_commands = commands.spawn_bundle(enemy);
+
+    enemy_commands
+        .insert(definition.collider(vec![CollisionMask::ENEMY]))
+        .insert(Health::new(modifiers.scale_health(archetype.max_health())))
+        .insert(Bounty(archetype.bounty()))
+        .insert(ScoreValue(archetype.score()))
+        .insert(LootDropper::new(archetype.loot_table()))
         .insert_bundle(TransformBundle::from_transform(Transform {
-            translation: Vec2::new_random_signed(&200.0, &300.0).extend(0.0),
+            translation: position.extend(layers.units),
             ..Default::default()
-        })); // FIXME z layering needs to be read fromsome reasource
+        }));
+
+    if let Some(behavior_tree) = archetype.behavior_tree() {
+        enemy_commands
+            .remove::<Idle>()
+            .insert(BehaviorTreeAgent::new(behavior_tree))
+            .insert(Blackboard::default());
+    }
+
+    if let Some(mut weapon) = archetype.weapon() {
+        weapon.damage = modifiers.scale_damage(weapon.damage);
+
+        enemy_commands.insert(weapon);
+    }
+
+    enemy_commands.id()
+}
+
+/// Enemy tanks share the player's `TankBundle` and turret, AI drives them through their action states
+pub fn spawn_enemy_tank(
+    position: Vec2,
+    modifiers: &DifficultyModifiers,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let archetype = EnemyArchetype::Tank;
+
+    let body = sprites.get(archetype.sprite_definition());
+
+    // the player's cannon, only aimed at the other side
+    let mut weapon = Weapon::tank_cannon();
+
+    weapon.targets = vec![CollisionMask::PLAYER];
+    weapon.damage = modifiers.scale_damage(weapon.damage);
+
+    let tank_turret = spawn_tank_turret(commands, layers, sprites, body.turret_pivot(), weapon);
+
+    commands
+        .entity(tank_turret)
+        .insert(TankTurret::default())
+        .insert(TurretAim::default())
+        .insert(ActionState::<PlayerAction>::default());
+
+    let mut tank = TankBundle::new(body);
+
+    tank.movable.speed = modifiers.scale_speed(archetype.speed());
+    tank.tank_body.sprite.transform.translation = position.extend(layers.units);
+
+    commands
+        .spawn()
+        .insert_bundle(tank)
+        .insert(Enemy)
+        .insert(archetype)
+        .insert(DisplayName(archetype.name().to_string()))
+        .insert(archetype.perception())
+        .insert(EnemyTankAi::default())
+        .insert(ActionState::<PlayerAction>::default())
+        .insert(body.collider(vec![CollisionMask::ENEMY]))
+        .insert(Health::new(modifiers.scale_health(archetype.max_health())))
+        .insert(Bounty(archetype.bounty()))
+        .insert(ScoreValue(archetype.score()))
+        .insert(LootDropper::new(archetype.loot_table()))
+        .add_child(tank_turret)
+        .id()
 }
 
 pub fn log_enemies_on_spawn(query: Query<&DisplayName, Added<Enemy>>) {
@@ -35,46 +132,71 @@ pub fn log_enemies_on_spawn(query: Query<&DisplayName, Added<Enemy>>) {
     }
 }
 
-pub fn spawn_player(mut commands: Commands) {
+pub fn spawn_player_tank(
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let body = sprites.get(TANK_BODY_SPRITE);
+
     // FIXME implement spawner functions for tank and tank tower instead of relying on TankTurretBundle and TankBundle
-    let tank_turret = spawn_tank_turret(&mut commands);
+    let tank_turret = spawn_tank_turret(
+        commands,
+        layers,
+        sprites,
+        body.turret_pivot(),
+        Weapon::tank_cannon(),
+    );
+
+    commands
+        .entity(tank_turret)
+        .insert(TankTurret::default())
+        .insert(MouseControlled::default())
+        .insert(Ammo::new(PLAYER_MAX_AMMO))
+        .insert_bundle(get_input_manager());
+
+    let mut tank = TankBundle::new(body);
+
+    tank.tank_body.sprite.transform.translation = position.extend(layers.units);
 
     commands
         .spawn()
-        .insert_bundle(TankBundle::new())
+        .insert_bundle(tank)
         .insert(PlayerControlled::default())
+        .insert(LOCAL_PLAYER)
+        .insert(PerceptionTarget::default())
+        .insert(body.collider(vec![CollisionMask::PLAYER]))
+        .insert(Health::new(TANK_MAX_HEALTH))
+        .insert(PowerUps::default())
+        .insert(RepairTool::default())
+        .insert(SelfRepair::default())
+        .insert(VisionSource::new(PLAYER_VISION_RADIUS))
         .insert_bundle(get_input_manager())
-        .add_child(tank_turret);
-
-    info!("Spawned player");
+        .add_child(tank_turret)
+        .id()
 }
 
-pub fn spawn_tank_turret(commands: &mut Commands) -> Entity {
-    let tank_turret_sprite = Sprite {
-        color: Color::rgb(0.0, 1.0, 0.0),
-        custom_size: Some(Vec2 {
-            x: 2.0 * 32.0,
-            y: 32.0,
-        }), // FIXME asset sizes should be in a bevy asset
-        // FIXME here manually set turret size, probably should set it with code to support different tank component sizes
-        anchor: Anchor::CenterRight,
-        ..Default::default()
-    };
-
-    let tank_turret_transform = Transform {
-        translation: Vec3::new(0., 10., 1.),
-        ..Default::default()
-    };
+/// Turret mounted at `pivot` of the body carrying it, it rotates around the anchor of its sprite
+pub fn spawn_tank_turret(
+    commands: &mut Commands,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    pivot: Vec2,
+    weapon: Weapon,
+) -> Entity {
+    let mut tank_turret_sprite = sprites.get(TANK_TURRET_SPRITE).sprite_bundle();
+
+    tank_turret_sprite.transform.translation = pivot.extend(layers.turret_offset());
 
     let tank_tower = commands
         .spawn()
-        .insert_bundle(SpriteBundle {
-            sprite: tank_turret_sprite,
-            transform: tank_turret_transform,
-            ..Default::default()
+        .insert_bundle(tank_turret_sprite)
+        .insert(weapon)
+        .insert(Movable {
+            speed: 0,
+            rotation_speed_rad: f32::to_radians(TURRET_ROTATION_SPEED_DEG),
         })
-        .insert(MouseControlled::default())
-        .insert_bundle(get_input_manager())
         .id();
 
     return tank_tower;
@@ -82,40 +204,46 @@ pub fn spawn_tank_turret(commands: &mut Commands) -> Entity {
 
 pub fn create_projectile(
     commands: &mut Commands,
-    sprite: Sprite,
-    translation: Vec3,
-    rotation: Quat,
+    definition: &SpriteDefinition,
+    transform: Transform,
     duration_sec: f32,
     projectile_speed: f32,
+    damage: u16,
+    collision_mask: Vec<CollisionMask>,
 ) -> Entity {
+    let mut sprite = definition.sprite_bundle();
+
+    sprite.transform = transform;
+
     let projectile = commands
         .spawn()
-        .insert(Projectile::default())
-        .insert(Collider::new(vec![CollisionMask::ENEMY]))
+        .insert(Projectile { damage })
+        .insert(definition.collider(collision_mask))
         .insert(DirectedLinearMove::move_forwards_with_speed(
-            rotation,
+            transform.rotation,
             projectile_speed,
         ))
         .insert(Lifetime::new(duration_sec))
-        .insert_bundle(SpriteBundle {
-            sprite,
-            transform: Transform {
-                translation,
-                rotation,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
+        .insert_bundle(sprite)
         .id();
 
     return projectile;
 }
 
 #[derive(Component, Default)]
-pub struct HomeTowardsEnemies {}
+pub struct HomeTowardsEnemies {
+    /// Enemy followed while it is alive, the nearest one is chased without or after it
+    pub target: Option<Entity>,
+}
 
 impl HomeTowardsEnemies {
     pub fn home_towards_nearest_enemy() -> Self {
         HomeTowardsEnemies { ..default() }
     }
+
+    pub fn home_towards(target: Entity) -> Self {
+        HomeTowardsEnemies {
+            target: Some(target),
+        }
+    }
 }
@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{CompressedImageFormats, ImageType},
+    },
+    sprite::Anchor,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{
+    enemy::EnemyArchetype,
+    prop::PropKind,
+    shared::{Collider, CollisionMask},
+};
+
+pub const TANK_BODY_SPRITE: &str = "sprites/tank_body.sprite.ron";
+pub const TANK_TURRET_SPRITE: &str = "sprites/tank_turret.sprite.ron";
+pub const TOWER_SPRITE: &str = "sprites/tower.sprite.ron";
+pub const PROJECTILE_SPRITE: &str = "sprites/projectile.sprite.ron";
+pub const BOSS_PROJECTILE_SPRITE: &str = "sprites/boss_projectile.sprite.ron";
+pub const HEADQUARTERS_SPRITE: &str = "sprites/headquarters.sprite.ron";
+pub const SPAWN_POINT_SPRITE: &str = "sprites/spawn_point.sprite.ron";
+pub const PICKUP_SPRITE: &str = "sprites/pickup.sprite.ron";
+pub const BUILD_GHOST_SPRITE: &str = "sprites/build_ghost.sprite.ron";
+pub const JUGGERNAUT_SPRITE: &str = "sprites/juggernaut.sprite.ron";
+pub const WALL_SPRITE: &str = "sprites/wall.sprite.ron";
+pub const WRECK_SPRITE: &str = "sprites/wreck.sprite.ron";
+
+/// Point of the sprite placed at the entity's translation, turrets rotate around it
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum SpriteAnchor {
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    CenterLeft,
+    CenterRight,
+    TopLeft,
+    TopCenter,
+    TopRight,
+}
+
+impl Default for SpriteAnchor {
+    fn default() -> Self {
+        SpriteAnchor::Center
+    }
+}
+
+impl SpriteAnchor {
+    pub fn anchor(&self) -> Anchor {
+        match self {
+            SpriteAnchor::Center => Anchor::Center,
+            SpriteAnchor::BottomLeft => Anchor::BottomLeft,
+            SpriteAnchor::BottomCenter => Anchor::BottomCenter,
+            SpriteAnchor::BottomRight => Anchor::BottomRight,
+            SpriteAnchor::CenterLeft => Anchor::CenterLeft,
+            SpriteAnchor::CenterRight => Anchor::CenterRight,
+            SpriteAnchor::TopLeft => Anchor::TopLeft,
+            SpriteAnchor::TopCenter => Anchor::TopCenter,
+            SpriteAnchor::TopRight => Anchor::TopRight,
+        }
+    }
+}
+
+/// Pixel rectangle of an atlas texture, `min` is the top left corner
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct AtlasRegion {
+    pub min: (u32, u32),
+    pub size: (u32, u32),
+}
+
+impl AtlasRegion {
+    /// Copies the region out of the atlas, so it is drawn like any other texture
+    fn cut_from(&self, atlas: &Image) -> Result<Image, anyhow::Error> {
+        let atlas = match atlas.convert(TextureFormat::Rgba8UnormSrgb) {
+            Some(atlas) => atlas,
+            None => anyhow::bail!("Unsupported atlas texture format"),
+        };
+
+        let atlas_width = atlas.texture_descriptor.size.width;
+        let atlas_height = atlas.texture_descriptor.size.height;
+
+        let (x, y) = self.min;
+        let (width, height) = self.size;
+
+        if x + width > atlas_width || y + height > atlas_height {
+            anyhow::bail!(
+                "Region {:?} is outside of the {}x{} atlas",
+                self,
+                atlas_width,
+                atlas_height
+            );
+        }
+
+        const BYTES_PER_PIXEL: usize = 4;
+
+        let mut data = Vec::with_capacity((width * height) as usize * BYTES_PER_PIXEL);
+
+        for row in y..y + height {
+            let start = (row * atlas_width + x) as usize * BYTES_PER_PIXEL;
+
+            data.extend_from_slice(&atlas.data[start..start + width as usize * BYTES_PER_PIXEL]);
+        }
+
+        let region = Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+
+        return Ok(region);
+    }
+}
+
+/// Look of a sprite as written in `.sprite.ron` asset files. Colliders are sized from it as well,
+/// so what is drawn is what gets hit.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "2f0b7c55-91d4-4e6a-b8a3-5c7e14d9a260"]
+pub struct SpriteDefinition {
+    /// Image relative to the assets folder, untextured sprites are drawn as a plain colored box
+    #[serde(default)]
+    pub texture: Option<String>,
+    /// Part of `texture` to draw when it is an atlas
+    #[serde(default)]
+    pub atlas_region: Option<AtlasRegion>,
+    pub size: (f32, f32),
+    #[serde(default)]
+    pub anchor: SpriteAnchor,
+    /// Where a turret is mounted, relative to the center of the sprite
+    #[serde(default)]
+    pub turret_pivot: (f32, f32),
+    /// Tints the texture, or is the whole look of untextured sprites
+    #[serde(default = "SpriteDefinition::default_color")]
+    pub color: (f32, f32, f32),
+    /// Resolved by the loader from `texture` and `atlas_region`
+    #[serde(skip)]
+    image: Option<Handle<Image>>,
+}
+
+impl Default for SpriteDefinition {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            atlas_region: None,
+            size: (64.0, 64.0),
+            anchor: SpriteAnchor::Center,
+            turret_pivot: (0.0, 0.0),
+            color: SpriteDefinition::default_color(),
+            image: None,
+        }
+    }
+}
+
+impl SpriteDefinition {
+    fn default_color() -> (f32, f32, f32) {
+        (1.0, 1.0, 1.0)
+    }
+
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.size.0, self.size.1)
+    }
+
+    pub fn turret_pivot(&self) -> Vec2 {
+        Vec2::new(self.turret_pivot.0, self.turret_pivot.1)
+    }
+
+    pub fn color(&self) -> Color {
+        Color::rgb(self.color.0, self.color.1, self.color.2)
+    }
+
+    pub fn sprite(&self) -> Sprite {
+        Sprite {
+            color: self.color(),
+            custom_size: Some(self.size()),
+            anchor: self.anchor.anchor(),
+            ..Default::default()
+        }
+    }
+
+    pub fn sprite_bundle(&self) -> SpriteBundle {
+        let mut bundle = SpriteBundle {
+            sprite: self.sprite(),
+            ..Default::default()
+        };
+
+        if let Some(image) = &self.image {
+            bundle.texture = image.clone();
+        }
+
+        return bundle;
+    }
+
+    pub fn collider(&self, collision_mask: Vec<CollisionMask>) -> Collider {
+        Collider::new(collision_mask, self.size())
+    }
+}
+
+#[derive(Default)]
+pub struct SpriteDefinitionLoader;
+
+impl AssetLoader for SpriteDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut definition = ron::de::from_bytes::<SpriteDefinition>(bytes)?;
+
+            let mut dependencies = Vec::new();
+
+            match (definition.texture.clone(), definition.atlas_region) {
+                (Some(texture), Some(region)) => {
+                    let atlas_bytes = load_context.read_asset_bytes(&texture).await?;
+
+                    let extension = Path::new(&texture)
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .unwrap_or_default();
+
+                    let atlas = Image::from_buffer(
+                        &atlas_bytes,
+                        ImageType::Extension(extension),
+                        CompressedImageFormats::NONE,
+                        true,
+                    )?;
+
+                    let region_image = region.cut_from(&atlas)?;
+
+                    definition.image = Some(
+                        load_context.set_labeled_asset("region", LoadedAsset::new(region_image)),
+                    );
+                }
+                (Some(texture), None) => {
+                    definition.image = Some(load_context.get_handle(texture.as_str()));
+
+                    dependencies.push(AssetPath::from(texture.as_str()).to_owned());
+                }
+                (None, Some(_)) => warn!("Atlas region without a texture is ignored"),
+                (None, None) => {}
+            }
+
+            let mut asset = LoadedAsset::new(definition);
+
+            for dependency in dependencies {
+                asset = asset.with_dependency(dependency);
+            }
+
+            load_context.set_default_asset(asset);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sprite.ron"]
+    }
+}
+
+/// Sprite definitions by path, copied out of their assets so spawners can use them without
+/// reaching into `Assets`. Anything not loaded falls back to a plain 64x64 box.
+#[derive(Default)]
+pub struct SpriteDefinitions {
+    handles: HashMap<String, Handle<SpriteDefinition>>,
+    loaded: HashMap<String, SpriteDefinition>,
+    /// Paths that failed to load and were already reported
+    failed: HashSet<String>,
+    fallback: SpriteDefinition,
+}
+
+impl SpriteDefinitions {
+    pub fn get(&self, path: &str) -> &SpriteDefinition {
+        self.loaded.get(path).unwrap_or(&self.fallback)
+    }
+
+    /// Whether every definition is either loaded or failed to, failed ones keep using the default
+    pub fn is_settled(&self, asset_server: &AssetServer) -> bool {
+        self.handles.iter().all(|(path, handle)| {
+            self.loaded.contains_key(path)
+                || asset_server.get_load_state(handle) == LoadState::Failed
+        })
+    }
+}
+
+pub fn load_sprite_definitions(
+    mut definitions: ResMut<SpriteDefinitions>,
+    asset_server: Res<AssetServer>,
+) {
+    let archetypes = [
+        EnemyArchetype::Grunt,
+        EnemyArchetype::Scout,
+        EnemyArchetype::Heavy,
+        EnemyArchetype::Tank,
+    ];
+
+    let paths = archetypes
+        .iter()
+        .map(|archetype| archetype.sprite_definition())
+        .chain(PropKind::ALL.iter().map(|kind| kind.sprite_definition()))
+        .chain([
+            TANK_BODY_SPRITE,
+            TANK_TURRET_SPRITE,
+            TOWER_SPRITE,
+            PROJECTILE_SPRITE,
+            BOSS_PROJECTILE_SPRITE,
+            HEADQUARTERS_SPRITE,
+            SPAWN_POINT_SPRITE,
+            PICKUP_SPRITE,
+            BUILD_GHOST_SPRITE,
+            JUGGERNAUT_SPRITE,
+            WALL_SPRITE,
+            WRECK_SPRITE,
+        ]);
+
+    for path in paths {
+        definitions
+            .handles
+            .insert(path.to_string(), asset_server.load(path));
+    }
+}
+
+/// Copies loaded and hot reloaded definitions, only entities spawned afterwards pick up changes.
+/// Definitions that fail to load are reported once and drawn with the default.
+pub fn update_sprite_definitions(
+    mut asset_events: EventReader<AssetEvent<SpriteDefinition>>,
+    assets: Res<Assets<SpriteDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut definitions: ResMut<SpriteDefinitions>,
+) {
+    let newly_failed: Vec<String> = definitions
+        .handles
+        .iter()
+        .filter(|(path, handle)| {
+            !definitions.failed.contains(*path)
+                && asset_server.get_load_state(*handle) == LoadState::Failed
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    for path in newly_failed {
+        warn!("Sprite definition {} failed to load, using the default", path);
+
+        definitions.failed.insert(path);
+    }
+
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        let path = definitions
+            .handles
+            .iter()
+            .find(|(_, definition_handle)| *definition_handle == handle)
+            .map(|(path, _)| path.clone());
+
+        if let (Some(path), Some(definition)) = (path, assets.get(handle)) {
+            definitions.loaded.insert(path, definition.clone());
+        }
+    }
+}
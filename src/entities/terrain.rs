@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use super::{
+    navigation::NavGrid,
+    projectiles::Slowed,
+    shared::{Movable, Solid},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Terrain {
+    Grass,
+    Road,
+    Mud,
+    Sand,
+    ShallowWater,
+    /// Impassable for everything, blocks sight and shots
+    Rock,
+}
+
+impl Terrain {
+    /// Fastest speed multiplier of any terrain, keeps the pathfinding heuristic from overestimating
+    pub const MAX_SPEED_MULTIPLIER: f32 = 1.25;
+
+    pub fn is_blocking(&self) -> bool {
+        *self == Terrain::Rock
+    }
+
+    /// Multiplier of `Movable.speed` while standing on the terrain
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            Terrain::Grass => 1.0,
+            Terrain::Road => Self::MAX_SPEED_MULTIPLIER,
+            Terrain::Mud => 0.5,
+            Terrain::Sand => 0.75,
+            Terrain::ShallowWater => 0.6,
+            Terrain::Rock => 0.0,
+        }
+    }
+
+    /// Multiplier of `Movable.rotation_speed_rad` while standing on the terrain
+    pub fn rotation_multiplier(&self) -> f32 {
+        match self {
+            Terrain::Grass | Terrain::Road => 1.0,
+            Terrain::Mud => 0.7,
+            Terrain::Sand => 0.9,
+            Terrain::ShallowWater => 0.8,
+            Terrain::Rock => 0.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Terrain::Grass => Color::rgb(0.25, 0.45, 0.2),
+            Terrain::Road => Color::rgb(0.45, 0.42, 0.38),
+            Terrain::Mud => Color::rgb(0.35, 0.25, 0.15),
+            Terrain::Sand => Color::rgb(0.76, 0.7, 0.45),
+            Terrain::ShallowWater => Color::rgb(0.25, 0.45, 0.65),
+            Terrain::Rock => Color::rgb(0.3, 0.3, 0.32),
+        }
+    }
+}
+
+/// Terrain currently changing the speed of a mover, `Movable` is restored to the base values when it
+/// gets back onto grass
+#[derive(Component)]
+pub struct TerrainEffect {
+    pub terrain: Terrain,
+    pub base_speed: i32,
+    pub base_rotation_speed_rad: f32,
+}
+
+impl TerrainEffect {
+    /// Replaces the base values the terrain scales, for speed changes that happen while on slow ground
+    pub fn rebase(&mut self, base_speed: i32, base_rotation_speed_rad: f32, movable: &mut Movable) {
+        self.base_speed = base_speed;
+        self.base_rotation_speed_rad = base_rotation_speed_rad;
+
+        self.apply(movable);
+    }
+
+    fn apply(&self, movable: &mut Movable) {
+        movable.speed = (self.base_speed as f32 * self.terrain.speed_multiplier()).round() as i32;
+        movable.rotation_speed_rad =
+            self.base_rotation_speed_rad * self.terrain.rotation_multiplier();
+    }
+}
+
+/// Scales the speed of tanks and enemies by the terrain under them. Slowed movers keep their speed
+/// until the slow wears off, so both do not restore each other's stale values.
+#[allow(clippy::type_complexity)]
+pub fn apply_terrain_effects(
+    mut movers: Query<
+        (Entity, &Transform, &mut Movable, Option<&mut TerrainEffect>),
+        (Without<Parent>, Without<Solid>, Without<Slowed>),
+    >,
+    nav_grid: Res<NavGrid>,
+    mut commands: Commands,
+) {
+    for (mover, transform, mut movable, effect) in movers.iter_mut() {
+        let terrain = nav_grid.terrain(nav_grid.cell(transform.translation.truncate()));
+
+        let neutral = terrain.speed_multiplier() == 1.0 && terrain.rotation_multiplier() == 1.0;
+
+        match effect {
+            Some(effect) if effect.terrain == terrain => {}
+            Some(effect) if neutral => {
+                movable.speed = effect.base_speed;
+                movable.rotation_speed_rad = effect.base_rotation_speed_rad;
+
+                commands.entity(mover).remove::<TerrainEffect>();
+            }
+            Some(mut effect) => {
+                effect.terrain = terrain;
+                effect.apply(&mut movable);
+            }
+            None if neutral => {}
+            None => {
+                let effect = TerrainEffect {
+                    terrain,
+                    base_speed: movable.speed,
+                    base_rotation_speed_rad: movable.rotation_speed_rad,
+                };
+
+                effect.apply(&mut movable);
+
+                commands.entity(mover).insert(effect);
+            }
+        }
+    }
+}
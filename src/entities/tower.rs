@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    asset::{AssetLoader, HandleId, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_transform_utils::get_angle_from_transform;
+use serde::Deserialize;
+
+use super::{
+    ai::perception::{GunfireEvent, PerceptionTarget},
+    build::{Structure, StructureKind},
+    economy::{Bank, TransactionReason},
+    enemy::Enemy,
+    fog::VisionSource,
+    layers::ZLayers,
+    navigation::NavBlocker,
+    player::LOCAL_PLAYER,
+    projectiles::{Explosive, FiredBy, SlowOnHit},
+    shared::{CollisionMask, EntitySharedBundle, Health, Movable},
+    spawner::spawn_tank_turret,
+    sprites::{SpriteDefinitions, TOWER_SPRITE},
+    weapons::{spawn_weapon_projectile, Weapon},
+};
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum TowerKind {
+    Gun,
+    Missile,
+    Slow,
+}
+
+const TOWER_DEFINITIONS: [&str; 3] = [
+    "towers/gun.tower.ron",
+    "towers/missile.tower.ron",
+    "towers/slow.tower.ron",
+];
+
+/// Weapon of a tower as written in its definition, every tower weapon targets enemies
+#[derive(Clone, Debug, Deserialize)]
+pub struct TowerWeaponDefinition {
+    pub cooldown_sec: f32,
+    pub damage: u16,
+    pub projectile_speed: f32,
+    #[serde(default)]
+    pub homing: bool,
+    #[serde(default)]
+    pub explosive: Option<Explosive>,
+    #[serde(default)]
+    pub slow: Option<SlowOnHit>,
+}
+
+/// Tower as written in `.tower.ron` asset files
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "8d3e6a41-5c27-4f0b-9e1a-7b62c4d0f9a3"]
+pub struct TowerDefinition {
+    pub kind: TowerKind,
+    pub name: String,
+    pub cost: u32,
+    pub color: (f32, f32, f32),
+    pub health: u16,
+    pub range: f32,
+    pub turn_speed_deg: f32,
+    pub weapon: TowerWeaponDefinition,
+}
+
+impl TowerDefinition {
+    pub fn color(&self) -> Color {
+        Color::rgb(self.color.0, self.color.1, self.color.2)
+    }
+
+    pub fn turn_speed_rad(&self) -> f32 {
+        f32::to_radians(self.turn_speed_deg)
+    }
+
+    pub fn weapon(&self) -> Weapon {
+        let definition = &self.weapon;
+
+        Weapon {
+            homing: definition.homing,
+            explosive: definition.explosive,
+            slow: definition.slow,
+            ..Weapon::new(
+                definition.cooldown_sec,
+                definition.damage,
+                definition.projectile_speed,
+                vec![CollisionMask::ENEMY],
+            )
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TowerDefinitionLoader;
+
+impl AssetLoader for TowerDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<TowerDefinition>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(definition));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tower.ron"]
+    }
+}
+
+/// Tower definitions by kind, copied out of their assets. Towers without a loaded definition
+/// cannot be built.
+#[derive(Default)]
+pub struct TowerDefinitions {
+    handles: Vec<Handle<TowerDefinition>>,
+    loaded: HashSet<HandleId>,
+    pub towers: HashMap<TowerKind, TowerDefinition>,
+}
+
+impl TowerDefinitions {
+    pub fn get(&self, kind: TowerKind) -> Option<&TowerDefinition> {
+        self.towers.get(&kind)
+    }
+
+    /// Whether every definition is either loaded or failed to
+    pub fn is_settled(&self, asset_server: &AssetServer) -> bool {
+        self.handles.iter().all(|handle| {
+            self.loaded.contains(&handle.id)
+                || asset_server.get_load_state(handle) == LoadState::Failed
+        })
+    }
+}
+
+pub fn load_tower_definitions(
+    mut definitions: ResMut<TowerDefinitions>,
+    asset_server: Res<AssetServer>,
+) {
+    definitions.handles = TOWER_DEFINITIONS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
+}
+
+/// Copies loaded and hot reloaded definitions, placed towers keep the stats they were built with
+pub fn update_tower_definitions(
+    mut asset_events: EventReader<AssetEvent<TowerDefinition>>,
+    assets: Res<Assets<TowerDefinition>>,
+    mut definitions: ResMut<TowerDefinitions>,
+) {
+    for event in asset_events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        if let Some(definition) = assets.get(handle) {
+            definitions.loaded.insert(handle.id);
+            definitions.towers.insert(definition.kind, definition.clone());
+        }
+    }
+}
+
+/// Stationary base of a tower, its turret is a child entity
+#[derive(Component)]
+pub struct Tower {
+    pub kind: TowerKind,
+}
+
+/// Turret that rotates towards the nearest enemy in range instead of the cursor
+#[derive(Component)]
+pub struct TowerTurret {
+    pub range: f32,
+    pub target: Option<Entity>,
+}
+
+pub struct PlaceTower {
+    pub kind: TowerKind,
+    pub position: Vec2,
+}
+
+/// Angle left to the target below which the tower fires
+const FIRING_TOLERANCE_RAD: f32 = 0.1;
+
+pub fn spawn_tower(
+    definition: &TowerDefinition,
+    kind: TowerKind,
+    position: Vec2,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let base = sprites.get(TOWER_SPRITE);
+
+    let turret = spawn_tank_turret(
+        commands,
+        layers,
+        sprites,
+        base.turret_pivot(),
+        definition.weapon(),
+    );
+
+    commands
+        .entity(turret)
+        .insert(TowerTurret {
+            range: definition.range,
+            target: None,
+        })
+        .insert(Movable {
+            speed: 0,
+            rotation_speed_rad: definition.turn_speed_rad(),
+        });
+
+    let mut shared = EntitySharedBundle::from_definition(&definition.name, base);
+
+    shared.sprite.sprite.color = definition.color();
+    shared.sprite.transform.translation = position.extend(layers.units);
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Tower { kind })
+        .insert(Health::new(definition.health))
+        .insert(base.collider(vec![CollisionMask::PLAYER]))
+        .insert(PerceptionTarget::default())
+        .insert(VisionSource::new(definition.range))
+        .insert(NavBlocker::default())
+        .insert(Structure {
+            kind: StructureKind::Tower(kind),
+            cost: definition.cost,
+        })
+        .add_child(turret)
+        .id()
+}
+
+pub fn place_towers(
+    mut place_requests: EventReader<PlaceTower>,
+    definitions: Res<TowerDefinitions>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut bank: Bank,
+    mut commands: Commands,
+) {
+    for request in place_requests.iter() {
+        let definition = match definitions.get(request.kind) {
+            Some(definition) => definition,
+            None => {
+                warn!("No tower definition for {:?}", request.kind);
+
+                continue;
+            }
+        };
+
+        let reason = TransactionReason::Tower(request.kind);
+
+        if let Err(error) = bank.try_purchase(definition.cost, reason) {
+            info!("Cannot afford {}: {:?}", definition.name, error);
+
+            continue;
+        }
+
+        spawn_tower(
+            definition,
+            request.kind,
+            request.position,
+            &layers,
+            &sprites,
+            &mut commands,
+        );
+
+        info!("Placed {} at {}", definition.name, request.position);
+    }
+}
+
+pub fn acquire_tower_targets(
+    mut turrets: Query<(&GlobalTransform, &mut TowerTurret)>,
+    enemies: Query<(Entity, &GlobalTransform), With<Enemy>>,
+) {
+    for (turret_transform, mut turret) in turrets.iter_mut() {
+        let position = turret_transform.translation().truncate();
+
+        let range = turret.range;
+
+        let in_range = |enemy_position: Vec2| enemy_position.distance(position) <= range;
+
+        let current_target_valid = turret
+            .target
+            .and_then(|target| enemies.get(target).ok())
+            .map(|(_, enemy_transform)| in_range(enemy_transform.translation().truncate()))
+            .unwrap_or(false);
+
+        if current_target_valid {
+            continue;
+        }
+
+        turret.target = enemies
+            .iter()
+            .map(|(enemy, enemy_transform)| (enemy, enemy_transform.translation().truncate()))
+            .filter(|(_, enemy_position)| in_range(*enemy_position))
+            .min_by(|(_, a), (_, b)| a.distance(position).total_cmp(&b.distance(position)))
+            .map(|(enemy, _)| enemy);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn aim_and_fire_towers(
+    mut turrets: Query<(
+        Entity,
+        &mut Transform,
+        &GlobalTransform,
+        &TowerTurret,
+        &Movable,
+        &mut Weapon,
+        Option<&Parent>,
+    )>,
+    enemies: Query<&GlobalTransform, With<Enemy>>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut gunfire: EventWriter<GunfireEvent>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (turret_entity, mut transform, global_transform, turret, movable, mut weapon, parent) in
+        turrets.iter_mut()
+    {
+        let target_position = match turret.target.and_then(|target| enemies.get(target).ok()) {
+            Some(target_transform) => target_transform.translation().truncate(),
+            None => continue,
+        };
+
+        let world_transform = global_transform.compute_transform();
+
+        let angle = get_angle_from_transform(&world_transform, &target_position);
+
+        let max_turn = movable.rotation_speed_rad * time.delta_seconds();
+
+        transform.rotate_z(angle.clamp(-max_turn, max_turn));
+
+        if angle.abs() > FIRING_TOLERANCE_RAD || !weapon.try_fire() {
+            continue;
+        }
+
+        let rotation = world_transform.rotation * Quat::from_rotation_z(angle);
+
+        let muzzle = world_transform.translation.truncate();
+
+        let projectile =
+            spawn_weapon_projectile(&mut commands, &layers, &sprites, &weapon, muzzle, rotation);
+
+        commands.entity(projectile).insert(FiredBy {
+            player: LOCAL_PLAYER,
+            origin: muzzle,
+        });
+
+        // enemies that hear the shot go for the tower, not its turret
+        gunfire.send(GunfireEvent {
+            shooter: parent.map_or(turret_entity, |parent| parent.get()),
+            position: muzzle,
+            audible_radius: weapon.audible_radius,
+        });
+    }
+}
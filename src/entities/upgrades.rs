@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::{
+    economy::{Bank, InsufficientFunds, TransactionReason},
+    player::{
+        PlayerControlled, TankTurret, TANK_MAX_HEALTH, TANK_ROTATION_SPEED_DEG, TANK_SPEED,
+        TURRET_ROTATION_SPEED_DEG,
+    },
+    shared::{Health, Movable},
+    terrain::TerrainEffect,
+    weapons::Weapon,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum UpgradeBranch {
+    Hull,
+    Turret,
+    Weapon,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum UpgradeKind {
+    HullSpeed,
+    HullArmor,
+    HullMaxHealth,
+    TurretRotationSpeed,
+    TurretFireRate,
+    WeaponDamage,
+    WeaponProjectileSpeed,
+}
+
+#[derive(Clone)]
+pub struct UpgradeDefinition {
+    pub kind: UpgradeKind,
+    pub branch: UpgradeBranch,
+    pub name: String,
+    pub max_level: u32,
+    pub base_cost: u32,
+    pub cost_per_level: u32,
+    /// Upgrade and level that has to be bought before this one unlocks
+    pub requires: Option<(UpgradeKind, u32)>,
+}
+
+impl UpgradeDefinition {
+    fn new(
+        kind: UpgradeKind,
+        branch: UpgradeBranch,
+        name: &str,
+        max_level: u32,
+        base_cost: u32,
+    ) -> Self {
+        UpgradeDefinition {
+            kind,
+            branch,
+            name: name.to_string(),
+            max_level,
+            base_cost,
+            cost_per_level: base_cost / 2,
+            requires: None,
+        }
+    }
+
+    fn requires(mut self, kind: UpgradeKind, level: u32) -> Self {
+        self.requires = Some((kind, level));
+
+        self
+    }
+
+    /// Cost of buying `level`, levels start at 1
+    pub fn cost(&self, level: u32) -> u32 {
+        self.base_cost + self.cost_per_level * level.saturating_sub(1)
+    }
+}
+
+#[derive(Debug)]
+pub enum UpgradeError {
+    Unknown,
+    MaxLevel,
+    Locked { requires: UpgradeKind, level: u32 },
+    InsufficientFunds(InsufficientFunds),
+}
+
+/// Stats of the player tank after applying the bought upgrades to the base values
+#[derive(Clone, Copy, Debug)]
+pub struct TankStats {
+    pub speed: i32,
+    pub rotation_speed_rad: f32,
+    pub armor: u16,
+    pub max_health: u16,
+    pub turret_rotation_speed_rad: f32,
+    pub fire_cooldown_sec: f32,
+    pub damage: u16,
+    pub projectile_speed: f32,
+}
+
+/// Bought upgrade levels, kept for the whole run
+pub struct UpgradeTree {
+    pub definitions: Vec<UpgradeDefinition>,
+    pub levels: HashMap<UpgradeKind, u32>,
+}
+
+impl Default for UpgradeTree {
+    fn default() -> Self {
+        use UpgradeKind::*;
+
+        let hull = UpgradeBranch::Hull;
+        let turret = UpgradeBranch::Turret;
+        let weapon = UpgradeBranch::Weapon;
+
+        UpgradeTree {
+            definitions: vec![
+                UpgradeDefinition::new(HullSpeed, hull, "Engine", 3, 80),
+                UpgradeDefinition::new(HullMaxHealth, hull, "Reinforced Hull", 3, 100),
+                UpgradeDefinition::new(HullArmor, hull, "Armor Plating", 3, 150)
+                    .requires(HullMaxHealth, 1),
+                UpgradeDefinition::new(TurretRotationSpeed, turret, "Turret Drive", 3, 60),
+                UpgradeDefinition::new(TurretFireRate, turret, "Autoloader", 3, 150)
+                    .requires(TurretRotationSpeed, 1),
+                UpgradeDefinition::new(WeaponDamage, weapon, "Heavy Shells", 3, 120),
+                UpgradeDefinition::new(WeaponProjectileSpeed, weapon, "Long Barrel", 3, 80)
+                    .requires(WeaponDamage, 1),
+            ],
+            levels: HashMap::new(),
+        }
+    }
+}
+
+impl UpgradeTree {
+    pub fn level(&self, kind: UpgradeKind) -> u32 {
+        self.levels.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn definition(&self, kind: UpgradeKind) -> Option<&UpgradeDefinition> {
+        self.definitions.iter().find(|definition| definition.kind == kind)
+    }
+
+    pub fn try_upgrade(
+        &mut self,
+        kind: UpgradeKind,
+        bank: &mut Bank,
+    ) -> Result<u32, UpgradeError> {
+        let definition = self.definition(kind).ok_or(UpgradeError::Unknown)?;
+
+        if let Some((requires, level)) = definition.requires {
+            if self.level(requires) < level {
+                return Err(UpgradeError::Locked { requires, level });
+            }
+        }
+
+        let next_level = self.level(kind) + 1;
+
+        if next_level > definition.max_level {
+            return Err(UpgradeError::MaxLevel);
+        }
+
+        let reason = TransactionReason::Upgrade(definition.name.clone());
+
+        bank.try_purchase(definition.cost(next_level), reason)
+            .map_err(UpgradeError::InsufficientFunds)?;
+
+        self.levels.insert(kind, next_level);
+
+        return Ok(next_level);
+    }
+
+    pub fn tank_stats(&self) -> TankStats {
+        use UpgradeKind::*;
+
+        let base_weapon = Weapon::tank_cannon();
+
+        let level = |kind| self.level(kind) as f32;
+
+        let base_cooldown_sec = base_weapon.cooldown.duration().as_secs_f32();
+
+        TankStats {
+            speed: (TANK_SPEED as f32 * (1.0 + 0.15 * level(HullSpeed))).round() as i32,
+            rotation_speed_rad: f32::to_radians(TANK_ROTATION_SPEED_DEG)
+                * (1.0 + 0.1 * level(HullSpeed)),
+            armor: 2 * self.level(HullArmor) as u16,
+            max_health: TANK_MAX_HEALTH + 50 * self.level(HullMaxHealth) as u16,
+            turret_rotation_speed_rad: f32::to_radians(TURRET_ROTATION_SPEED_DEG)
+                * (1.0 + 0.25 * level(TurretRotationSpeed)),
+            fire_cooldown_sec: base_cooldown_sec / (1.0 + 0.2 * level(TurretFireRate)),
+            damage: (base_weapon.damage as f32 * (1.0 + 0.2 * level(WeaponDamage))).round() as u16,
+            projectile_speed: base_weapon.projectile_speed
+                * (1.0 + 0.25 * level(WeaponProjectileSpeed)),
+        }
+    }
+}
+
+pub struct PurchaseUpgrade(pub UpgradeKind);
+
+pub fn request_upgrades_from_keys(
+    keys: Res<Input<KeyCode>>,
+    mut purchases: EventWriter<PurchaseUpgrade>,
+) {
+    use UpgradeKind::*;
+
+    let bindings = [
+        (KeyCode::Key1, HullSpeed),
+        (KeyCode::Key2, HullMaxHealth),
+        (KeyCode::Key3, HullArmor),
+        (KeyCode::Key4, TurretRotationSpeed),
+        (KeyCode::Key5, TurretFireRate),
+        (KeyCode::Key6, WeaponDamage),
+        (KeyCode::Key7, WeaponProjectileSpeed),
+    ];
+
+    for (key, kind) in bindings {
+        if keys.just_pressed(key) {
+            purchases.send(PurchaseUpgrade(kind));
+        }
+    }
+}
+
+pub fn purchase_upgrades(
+    mut purchases: EventReader<PurchaseUpgrade>,
+    mut tree: ResMut<UpgradeTree>,
+    mut bank: Bank,
+) {
+    for PurchaseUpgrade(kind) in purchases.iter() {
+        match tree.try_upgrade(*kind, &mut bank) {
+            Ok(level) => info!("Upgraded {:?} to level {}", kind, level),
+            Err(error) => info!("Cannot upgrade {:?}: {:?}", kind, error),
+        }
+    }
+}
+
+/// Writes the upgraded stats into the player's components whenever the tree changes or a player tank spawns
+#[allow(clippy::type_complexity)]
+pub fn apply_tank_upgrades(
+    tree: Res<UpgradeTree>,
+    mut tanks: Query<
+        (&mut Movable, Option<&mut Health>, Option<&mut TerrainEffect>, &Children),
+        With<PlayerControlled>,
+    >,
+    added_tanks: Query<(), Added<PlayerControlled>>,
+    mut turrets: Query<
+        (&mut Movable, &mut Weapon),
+        (With<TankTurret>, Without<PlayerControlled>),
+    >,
+) {
+    if !tree.is_changed() && added_tanks.is_empty() {
+        return;
+    }
+
+    let stats = tree.tank_stats();
+
+    for (mut movable, health, terrain_effect, children) in tanks.iter_mut() {
+        // on slow ground the terrain keeps scaling the new base speed
+        match terrain_effect {
+            Some(mut effect) => {
+                effect.rebase(stats.speed, stats.rotation_speed_rad, &mut movable);
+            }
+            None => {
+                movable.speed = stats.speed;
+                movable.rotation_speed_rad = stats.rotation_speed_rad;
+            }
+        }
+
+        if let Some(mut health) = health {
+            let health_gained = stats.max_health.saturating_sub(health.max_health);
+
+            health.armor = stats.armor;
+            health.max_health = stats.max_health;
+            health.current_health = (health.current_health + health_gained).min(stats.max_health);
+        }
+
+        for child in children.iter() {
+            if let Ok((mut turret_movable, mut weapon)) = turrets.get_mut(*child) {
+                turret_movable.rotation_speed_rad = stats.turret_rotation_speed_rad;
+
+                weapon.set_cooldown(stats.fire_cooldown_sec);
+                weapon.damage = stats.damage;
+                weapon.projectile_speed = stats.projectile_speed;
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+
+use super::{
+    ai::perception::BlocksSight,
+    build::{Structure, StructureKind},
+    economy::{Bank, TransactionReason},
+    enemy::Enemy,
+    layers::ZLayers,
+    navigation::NavBlocker,
+    shared::{
+        Collider, CollisionMask, DamageEvent, DeathEvent, EntitySharedBundle, Health, Movable,
+        Solid,
+    },
+    sprites::{SpriteDefinitions, WALL_SPRITE},
+};
+
+pub const WALL_COST: u32 = 25;
+const WALL_HEALTH: u16 = 150;
+
+/// Damage an enemy pushing against a solid deals to it on every attack
+const BLOCKED_ATTACK_DAMAGE: u16 = 10;
+
+#[derive(Component, Default)]
+pub struct Wall;
+
+/// Enemy stuck against a destructible solid, it attacks the solid until it breaks or the enemy moves away
+#[derive(Component)]
+pub struct BlockedBy {
+    pub solid: Entity,
+    pub attack_timer: Timer,
+}
+
+impl BlockedBy {
+    pub fn new(solid: Entity) -> Self {
+        BlockedBy {
+            solid,
+            attack_timer: Timer::from_seconds(1.0, true),
+        }
+    }
+}
+
+pub struct PlaceWall {
+    pub position: Vec2,
+}
+
+pub fn spawn_wall(
+    position: Vec2,
+    cost: u32,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) -> Entity {
+    let definition = sprites.get(WALL_SPRITE);
+
+    let mut shared = EntitySharedBundle::from_definition("Wall", definition);
+
+    shared.sprite.transform.translation = position.extend(layers.units);
+
+    commands
+        .spawn_bundle(shared)
+        .insert(Wall)
+        .insert(Solid)
+        .insert(Health::new(WALL_HEALTH))
+        .insert(definition.collider(vec![CollisionMask::PLAYER, CollisionMask::ENEMY]))
+        .insert(BlocksSight)
+        .insert(NavBlocker)
+        .insert(Structure {
+            kind: StructureKind::Wall,
+            cost,
+        })
+        .id()
+}
+
+pub fn place_walls(
+    mut place_requests: EventReader<PlaceWall>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut bank: Bank,
+    mut commands: Commands,
+) {
+    for request in place_requests.iter() {
+        if let Err(error) = bank.try_purchase(WALL_COST, TransactionReason::Wall) {
+            info!("Cannot afford wall: {:?}", error);
+
+            continue;
+        }
+
+        spawn_wall(
+            request.position,
+            WALL_COST,
+            &layers,
+            &sprites,
+            &mut commands,
+        );
+    }
+}
+
+/// Overlap of two boxes along the axis where it is smallest, pointing from `solid` towards `mover`
+fn penetration(mover: Vec2, mover_size: Vec2, solid: Vec2, solid_size: Vec2) -> Option<Vec2> {
+    let delta = mover - solid;
+
+    let overlap = (mover_size + solid_size) / 2.0 - delta.abs();
+
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+
+    if overlap.x < overlap.y {
+        Some(Vec2::new(overlap.x * delta.x.signum(), 0.0))
+    } else {
+        Some(Vec2::new(0.0, overlap.y * delta.y.signum()))
+    }
+}
+
+/// Pushes everything that moves back out of solids after all movement of the frame happened
+#[allow(clippy::type_complexity)]
+pub fn push_movers_out_of_solids(
+    mut movers: Query<
+        (Entity, &mut Transform, &Collider, Option<&BlockedBy>, Option<&Enemy>),
+        (With<Movable>, Without<Solid>, Without<Parent>),
+    >,
+    solids: Query<(Entity, &Transform, &Collider, Option<&Health>), With<Solid>>,
+    mut commands: Commands,
+) {
+    for (mover, mut transform, collider, blocked_by, enemy) in movers.iter_mut() {
+        let mut blocking_solid = None;
+
+        for (solid, solid_transform, solid_collider, solid_health) in solids.iter() {
+            let push = penetration(
+                transform.translation.truncate(),
+                collider.size,
+                solid_transform.translation.truncate(),
+                solid_collider.size,
+            );
+
+            if let Some(push) = push {
+                transform.translation += push.extend(0.0);
+
+                if solid_health.is_some() {
+                    blocking_solid = Some(solid);
+                }
+            }
+        }
+
+        if enemy.is_none() {
+            continue;
+        }
+
+        match (blocking_solid, blocked_by) {
+            (Some(solid), Some(blocked_by)) if blocked_by.solid == solid => {}
+            (Some(solid), _) => {
+                commands.entity(mover).insert(BlockedBy::new(solid));
+            }
+            (None, Some(_)) => {
+                commands.entity(mover).remove::<BlockedBy>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+pub fn attack_blocking_solids(
+    mut attackers: Query<&mut BlockedBy, With<Enemy>>,
+    mut solids: Query<(&mut Health, &Transform), With<Solid>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for mut blocked_by in attackers.iter_mut() {
+        if !blocked_by.attack_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let (mut health, transform) = match solids.get_mut(blocked_by.solid) {
+            Ok(solid) => solid,
+            Err(_) => continue,
+        };
+
+        if health.is_dead() {
+            continue;
+        }
+
+        damage_events.send(DamageEvent {
+            target: blocked_by.solid,
+            amount: BLOCKED_ATTACK_DAMAGE,
+        });
+
+        if health.try_apply_damage(BLOCKED_ATTACK_DAMAGE).is_none() {
+            deaths.send(DeathEvent {
+                entity: blocked_by.solid,
+                position: transform.translation.truncate(),
+                killed_by: None,
+            });
+
+            commands.entity(blocked_by.solid).despawn_recursive();
+        }
+    }
+}
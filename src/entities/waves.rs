@@ -0,0 +1,434 @@
+use bevy::prelude::*;
+use bevy_rand_utils::RandomFromRange;
+
+use super::{
+    ai::{
+        enemy_ai::FollowPath,
+        squad::{spawn_squad, Formation, SquadSpawn},
+    },
+    boss::{spawn_boss, BossDefinition},
+    bounds::WorldBounds,
+    difficulty::{Difficulty, DifficultyModifiers},
+    enemy::{Enemy, EnemyArchetype},
+    layers::ZLayers,
+    level::CurrentLevel,
+    player::PlayerControlled,
+    spawn_points::{SpawnPoint, SpawnedBy},
+    spawner::spawn_enemy,
+    sprites::SpriteDefinitions,
+};
+
+#[derive(Clone)]
+pub struct Wave {
+    pub enemies: u32,
+    pub spawn_interval_sec: f32,
+    pub boss: Option<BossDefinition>,
+    pub squad: Option<SquadSpawn>,
+    /// Names of the spawn points this wave spawns from, any spawn point is used when empty
+    pub spawn_points: Vec<String>,
+}
+
+impl Wave {
+    pub fn targets(&self, spawn_point_name: &str) -> bool {
+        self.spawn_points.is_empty() || self.spawn_points.iter().any(|name| name == spawn_point_name)
+    }
+
+    /// Whether any targeted spawn point can still spawn while the wave runs as `wave_number`
+    pub fn can_spawn_from<'a>(
+        &self,
+        wave_number: u32,
+        spawn_points: impl IntoIterator<Item = &'a SpawnPoint>,
+    ) -> bool {
+        spawn_points.into_iter().any(|spawn_point| {
+            self.targets(&spawn_point.name) && spawn_point.can_spawn_in_wave(wave_number)
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WavePhase {
+    Break,
+    Spawning,
+    Fighting,
+}
+
+pub struct WaveCompleted {
+    pub wave_number: u32,
+    pub duration_sec: f32,
+}
+
+/// Runs the configured waves in order. Once all waves have been played the last one is repeated.
+pub struct WaveDirector {
+    pub waves: Vec<Wave>,
+    pub wave_number: u32,
+    pub phase: WavePhase,
+    break_timer: Timer,
+    spawn_timer: Timer,
+    enemies_spawned: u32,
+    wave_started_at: f64,
+}
+
+impl WaveDirector {
+    /// Panics without any waves, there would be nothing to repeat once they are played
+    pub fn new(waves: Vec<Wave>, break_duration_sec: f32) -> Self {
+        assert!(!waves.is_empty(), "Wave director needs at least one wave");
+
+        WaveDirector {
+            waves,
+            wave_number: 0,
+            phase: WavePhase::Break,
+            break_timer: Timer::from_seconds(break_duration_sec, false),
+            spawn_timer: Timer::default(),
+            enemies_spawned: 0,
+            wave_started_at: 0.0,
+        }
+    }
+
+    pub fn current_wave(&self) -> &Wave {
+        let wave_index = (self.wave_number.max(1) - 1) as usize;
+
+        &self.waves[wave_index.min(self.waves.len() - 1)]
+    }
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        WaveDirector::new(
+            vec![
+                Wave {
+                    enemies: 3,
+                    spawn_interval_sec: 1.5,
+                    boss: None,
+                    squad: None,
+                    spawn_points: vec!["north".to_string()],
+                },
+                Wave {
+                    enemies: 5,
+                    spawn_interval_sec: 1.0,
+                    boss: None,
+                    squad: Some(SquadSpawn {
+                        formation: Formation::Wedge,
+                        archetype: EnemyArchetype::Grunt,
+                        followers: 4,
+                    }),
+                    spawn_points: Vec::new(),
+                },
+                Wave {
+                    enemies: 4,
+                    spawn_interval_sec: 1.0,
+                    boss: Some(BossDefinition::juggernaut()),
+                    squad: Some(SquadSpawn {
+                        formation: Formation::Column,
+                        archetype: EnemyArchetype::Heavy,
+                        followers: 2,
+                    }),
+                    spawn_points: vec!["north".to_string(), "east".to_string()],
+                },
+            ],
+            3.0,
+        )
+    }
+}
+
+/// Spawns the squad of a wave at the targeted spawn point with the most room left. Followers are
+/// left out to stay within `SpawnPoint.max_alive`, the leader follows the path of the spawn point.
+#[allow(clippy::too_many_arguments)]
+fn spawn_squad_from_spawn_point(
+    squad: &SquadSpawn,
+    wave: &Wave,
+    spawn_points: &mut Query<(Entity, &mut SpawnPoint, &GlobalTransform)>,
+    spawned_enemies: &Query<&SpawnedBy>,
+    modifiers: &DifficultyModifiers,
+    bounds: &WorldBounds,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    commands: &mut Commands,
+) {
+    let room_left = |entity: Entity, spawn_point: &SpawnPoint| {
+        let alive = spawned_enemies
+            .iter()
+            .filter(|spawned_by| spawned_by.0 == entity)
+            .count();
+
+        spawn_point.max_alive.saturating_sub(alive)
+    };
+
+    let chosen = spawn_points
+        .iter()
+        .filter(|(_, spawn_point, _)| wave.targets(&spawn_point.name))
+        .map(|(entity, spawn_point, _)| (entity, room_left(entity, spawn_point)))
+        .filter(|(_, room)| *room > 0)
+        .max_by_key(|(_, room)| *room);
+
+    let (chosen, room) = match chosen {
+        Some(chosen) => chosen,
+        None => {
+            info!("No spawn point has room for the squad, it is not spawned");
+
+            return;
+        }
+    };
+
+    let (_, mut spawn_point, transform) = match spawn_points.get_mut(chosen) {
+        Ok(spawn_point) => spawn_point,
+        Err(_) => return,
+    };
+
+    let squad = SquadSpawn {
+        followers: squad.followers.min(room - 1),
+        ..*squad
+    };
+
+    let position = bounds.clamp_spawn(transform.translation().truncate());
+
+    let members = spawn_squad(&squad, position, modifiers, bounds, layers, sprites, commands);
+
+    for member in members.iter() {
+        commands.entity(*member).insert(SpawnedBy(chosen));
+    }
+
+    if !spawn_point.path.is_empty() {
+        commands
+            .entity(members[0])
+            .insert(FollowPath::new(spawn_point.path.clone()));
+    }
+
+    spawn_point.cooldown.reset();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_wave_director(
+    mut director: ResMut<WaveDirector>,
+    mut spawn_points: Query<(Entity, &mut SpawnPoint, &GlobalTransform)>,
+    spawned_enemies: Query<&SpawnedBy>,
+    enemies: Query<&Enemy>,
+    players: Query<&GlobalTransform, With<PlayerControlled>>,
+    difficulty: Res<Difficulty>,
+    bounds: Res<WorldBounds>,
+    level: Res<CurrentLevel>,
+    layers: Res<ZLayers>,
+    sprites: Res<SpriteDefinitions>,
+    mut wave_completed: EventWriter<WaveCompleted>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let director = &mut *director;
+
+    let modifiers = difficulty.modifiers();
+
+    match director.phase {
+        WavePhase::Break => {
+            // the first wave needs the spawn points of the level to spawn from
+            if !level.spawned {
+                return;
+            }
+
+            if !director.break_timer.tick(time.delta()).just_finished() {
+                return;
+            }
+
+            director.wave_number += 1;
+            director.enemies_spawned = 0;
+            director.phase = WavePhase::Spawning;
+            director.wave_started_at = time.seconds_since_startup();
+
+            let spawn_interval_sec = modifiers.scale_spawn_interval(director.current_wave().spawn_interval_sec);
+
+            director.spawn_timer = Timer::from_seconds(spawn_interval_sec, true);
+
+            info!("Wave {} begins", director.wave_number);
+
+            let wave = director.current_wave();
+
+            if let Some(boss) = &wave.boss {
+                let position = spawn_points
+                    .iter()
+                    .find(|(_, spawn_point, _)| wave.targets(&spawn_point.name))
+                    .map(|(_, _, transform)| transform.translation().truncate())
+                    .unwrap_or_else(|| Vec2::new_random_signed(&250.0, &300.0));
+
+                let position = bounds.clamp_spawn(position);
+
+                spawn_boss(boss, position, &modifiers, &layers, &sprites, &mut commands);
+            }
+
+            if let Some(squad) = &wave.squad {
+                spawn_squad_from_spawn_point(
+                    squad,
+                    wave,
+                    &mut spawn_points,
+                    &spawned_enemies,
+                    &modifiers,
+                    &bounds,
+                    &layers,
+                    &sprites,
+                    &mut commands,
+                );
+            }
+        }
+        WavePhase::Spawning => {
+            if !director.spawn_timer.tick(time.delta()).just_finished() {
+                return;
+            }
+
+            let wave = director.current_wave();
+
+            let spawnable = wave.can_spawn_from(
+                director.wave_number,
+                spawn_points.iter().map(|(_, spawn_point, _)| spawn_point),
+            );
+
+            // destroyed spawn points or ones that only open in later waves would stall the wave forever
+            if !spawnable {
+                info!("No spawn point can spawn wave {}, it stops spawning", director.wave_number);
+
+                director.phase = WavePhase::Fighting;
+
+                return;
+            }
+
+            let player_position = players
+                .get_single()
+                .ok()
+                .map(|player_transform| player_transform.translation().truncate());
+
+            let eligible_spawn_points: Vec<Entity> = spawn_points
+                .iter()
+                .filter(|(entity, spawn_point, transform)| {
+                    let alive = spawned_enemies
+                        .iter()
+                        .filter(|spawned_by| spawned_by.0 == *entity)
+                        .count();
+
+                    wave.targets(&spawn_point.name)
+                        && spawn_point.cooldown.finished()
+                        && alive < spawn_point.max_alive
+                        && spawn_point.is_active(
+                            director.wave_number,
+                            transform.translation().truncate(),
+                            player_position,
+                        )
+                })
+                .map(|(entity, _, _)| entity)
+                .collect();
+
+            if eligible_spawn_points.is_empty() {
+                return;
+            }
+
+            let chosen = eligible_spawn_points[fastrand::usize(..eligible_spawn_points.len())];
+
+            if let Ok((_, mut spawn_point, transform)) = spawn_points.get_mut(chosen) {
+                if let Some(archetype) = spawn_point.roll_archetype() {
+                    let position = bounds.clamp_spawn(
+                        transform.translation().truncate() + Vec2::new_random_signed(&10.0, &40.0),
+                    );
+
+                    let enemy = spawn_enemy(
+                        archetype,
+                        position,
+                        &modifiers,
+                        &layers,
+                        &sprites,
+                        &mut commands,
+                    );
+
+                    commands.entity(enemy).insert(SpawnedBy(chosen));
+
+                    if !spawn_point.path.is_empty() {
+                        commands
+                            .entity(enemy)
+                            .insert(FollowPath::new(spawn_point.path.clone()));
+                    }
+
+                    spawn_point.cooldown.reset();
+
+                    director.enemies_spawned += 1;
+                }
+            }
+
+            if director.enemies_spawned >= director.current_wave().enemies {
+                director.phase = WavePhase::Fighting;
+            }
+        }
+        WavePhase::Fighting => {
+            if enemies.is_empty() {
+                let duration_sec = (time.seconds_since_startup() - director.wave_started_at) as f32;
+
+                info!("Wave {} cleared in {:.1}s", director.wave_number, duration_sec);
+
+                wave_completed.send(WaveCompleted {
+                    wave_number: director.wave_number,
+                    duration_sec,
+                });
+
+                director.phase = WavePhase::Break;
+                director.break_timer.reset();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::spawn_points::{SpawnPointActivation, SpawnTableEntry};
+
+    fn wave(spawn_points: &[&str]) -> Wave {
+        Wave {
+            enemies: 3,
+            spawn_interval_sec: 1.0,
+            boss: None,
+            squad: None,
+            spawn_points: spawn_points.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    fn spawn_point(name: &str, activation: SpawnPointActivation) -> SpawnPoint {
+        let spawn_table = vec![SpawnTableEntry {
+            archetype: EnemyArchetype::Grunt,
+            weight: 1,
+        }];
+
+        SpawnPoint::new(name, spawn_table, 1.0, 3, activation)
+    }
+
+    #[test]
+    fn wave_cannot_spawn_from_spawn_points_opening_later() {
+        let spawn_points = [
+            spawn_point("north", SpawnPointActivation::FromWave(4)),
+            spawn_point("east", SpawnPointActivation::Always),
+        ];
+
+        let wave = wave(&["north"]);
+
+        assert!(!wave.can_spawn_from(2, spawn_points.iter()));
+        assert!(wave.can_spawn_from(4, spawn_points.iter()));
+    }
+
+    #[test]
+    fn wave_can_spawn_from_spawn_points_waiting_for_the_player() {
+        let activation = SpawnPointActivation::PlayerWithin(200.0);
+
+        let spawn_points = [spawn_point("north", activation)];
+
+        assert!(wave(&[]).can_spawn_from(1, spawn_points.iter()));
+    }
+
+    #[test]
+    fn wave_cannot_spawn_without_targeted_spawn_points() {
+        let spawn_points = [spawn_point("east", SpawnPointActivation::Always)];
+
+        assert!(!wave(&["north"]).can_spawn_from(1, spawn_points.iter()));
+        assert!(!wave(&[]).can_spawn_from(1, std::iter::empty()));
+    }
+
+    #[test]
+    fn spawn_point_without_weighted_enemies_cannot_spawn() {
+        let mut spawn_point = spawn_point("north", SpawnPointActivation::Always);
+
+        spawn_point.spawn_table[0].weight = 0;
+
+        assert!(!wave(&[]).can_spawn_from(1, [&spawn_point]));
+    }
+}
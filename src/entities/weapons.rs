@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use super::{
+    layers::ZLayers,
+    projectiles::{Explosive, SlowOnHit},
+    shared::CollisionMask,
+    spawner::{create_projectile, HomeTowardsEnemies},
+    sprites::{SpriteDefinitions, PROJECTILE_SPRITE},
+};
+
+const TANK_CANNON_COOLDOWN_SEC: f32 = 0.4;
+
+#[derive(Component, Clone)]
+pub struct Weapon {
+    pub cooldown: Timer,
+    pub damage: u16,
+    pub projectile_speed: f32,
+    pub projectile_lifetime_sec: f32,
+    /// Sprite definition of the projectiles, which also sizes their colliders
+    pub projectile_sprite: &'static str,
+    pub targets: Vec<CollisionMask>,
+    /// Enemies within this distance of the muzzle hear the shot
+    pub audible_radius: f32,
+    pub homing: bool,
+    pub explosive: Option<Explosive>,
+    pub slow: Option<SlowOnHit>,
+}
+
+impl Weapon {
+    pub fn new(
+        cooldown_sec: f32,
+        damage: u16,
+        projectile_speed: f32,
+        targets: Vec<CollisionMask>,
+    ) -> Self {
+        let mut cooldown = Timer::from_seconds(cooldown_sec, false);
+
+        cooldown.tick(cooldown.duration()); // weapons start loaded
+
+        Weapon {
+            cooldown,
+            damage,
+            projectile_speed,
+            projectile_lifetime_sec: 20.0,
+            projectile_sprite: PROJECTILE_SPRITE,
+            targets,
+            audible_radius: 400.0,
+            homing: false,
+            explosive: None,
+            slow: None,
+        }
+    }
+
+    /// Main gun of every tank, fires once per click when reloaded
+    pub fn tank_cannon() -> Self {
+        Weapon::new(TANK_CANNON_COOLDOWN_SEC, 25, 100.0, vec![CollisionMask::ENEMY])
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.cooldown.finished()
+    }
+
+    /// Consumes the loaded shot and restarts the cooldown. Returns false while still reloading.
+    pub fn try_fire(&mut self) -> bool {
+        if !self.cooldown.finished() {
+            return false;
+        }
+
+        self.cooldown.reset();
+
+        return true;
+    }
+
+    pub fn set_cooldown(&mut self, cooldown_sec: f32) {
+        self.cooldown.set_duration(Duration::from_secs_f32(cooldown_sec));
+    }
+}
+
+/// Limited shells for a weapon, weapons without it never run dry
+#[derive(Component)]
+pub struct Ammo {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Ammo {
+    pub fn new(max: u32) -> Self {
+        Ammo { current: max, max }
+    }
+
+    /// Adds up to `amount` shells without going over the maximum, returns how many were added
+    pub fn refill(&mut self, amount: u32) -> u32 {
+        let refilled = amount.min(self.max.saturating_sub(self.current));
+
+        self.current += refilled;
+
+        return refilled;
+    }
+}
+
+pub fn spawn_weapon_projectile(
+    commands: &mut Commands,
+    layers: &ZLayers,
+    sprites: &SpriteDefinitions,
+    weapon: &Weapon,
+    position: Vec2,
+    rotation: Quat,
+) -> Entity {
+    let projectile_transform = Transform {
+        translation: position.extend(layers.projectiles),
+        rotation,
+        ..Default::default()
+    };
+
+    let projectile = create_projectile(
+        commands,
+        sprites.get(weapon.projectile_sprite),
+        projectile_transform,
+        weapon.projectile_lifetime_sec,
+        weapon.projectile_speed,
+        weapon.damage,
+        weapon.targets.clone(),
+    );
+
+    if weapon.homing {
+        commands
+            .entity(projectile)
+            .insert(HomeTowardsEnemies::home_towards_nearest_enemy());
+    }
+
+    if let Some(explosive) = weapon.explosive {
+        commands.entity(projectile).insert(explosive);
+    }
+
+    if let Some(slow) = weapon.slow {
+        commands.entity(projectile).insert(slow);
+    }
+
+    return projectile;
+}
+
+pub fn tick_weapon_cooldowns(mut query: Query<&mut Weapon>, time: Res<Time>) {
+    for mut weapon in query.iter_mut() {
+        weapon.cooldown.tick(time.delta());
+    }
+}
@@ -1,6 +1,11 @@
 use bevy::{log::LogSettings, prelude::*};
 use bevy_mouse_position_component::{MousePosition2d, MousePositionPlugin};
-use entities::{player::PlayerAction, EntitiesPlugin};
+use entities::{
+    arena::ArenaParams,
+    level::CurrentLevel,
+    player::PlayerAction,
+    EntitiesPlugin,
+};
 use leafwing_input_manager::prelude::*;
 
 pub mod entities;
@@ -13,6 +18,7 @@ fn main() {
             filter: "debug,wgpu_core=warn,wgpu_hal=warn".into(),
             level: bevy::log::Level::DEBUG,
         })
+        .insert_resource(current_level_from_args())
         .add_plugin(InputManagerPlugin::<PlayerAction>::default())
         .add_plugin(MousePositionPlugin)
         .add_plugin(EntitiesPlugin)
@@ -20,6 +26,22 @@ fn main() {
         .run();
 }
 
+/// `--arena <seed>` plays a generated arena instead of the default map
+fn current_level_from_args() -> CurrentLevel {
+    let args: Vec<String> = std::env::args().collect();
+
+    let seed = args
+        .iter()
+        .position(|arg| arg == "--arena")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|seed| seed.parse::<u64>().ok());
+
+    match seed {
+        Some(seed) => CurrentLevel::arena(seed, ArenaParams::default()),
+        None => CurrentLevel::default(),
+    }
+}
+
 fn add_camera_with_tracking(mut commands: Commands) {
     commands
         .spawn()